@@ -1,30 +1,58 @@
+mod debug_view;
 mod dom_utils;
+mod fail_safe;
+mod frame_loop;
+mod geometry;
 mod laughing_man_image;
+mod overlay_assignment;
+mod overlay_registry;
+mod overlay_settings;
+mod performance_hud;
+mod playback_controls;
+mod redaction;
+mod roi_motion;
 mod side_menu;
+mod source_picker;
+mod still_image;
 mod video_face_tracker;
 
+use crate::error::AppError;
 use crate::Interval;
 use crate::IsSideMenuActive;
-use dom_utils::context2d;
-use laughing_man_image::{LaughingManImage, LaughingManOptions, LaughingManState};
+use debug_view::DebugViewOptions;
+use dom_utils::{context2d, download_url, AnimationFrameLoop, EventListener};
+use fail_safe::{FailSafe, FailSafeMode, FailSafeOptions};
+use frame_loop::FrameLoop;
+use geometry::{ObjectFit, Rect, Size, Transform};
+use laughing_man_image::{
+    LaughingManCanvasRenderer, LaughingManImage, LaughingManOptions, LaughingManState,
+    OverlayAspect, OverlayFit, OverlayRenderer, OverlayTransition,
+};
+use overlay_assignment::{OverlayAssigner, OverlayAssignment};
+use overlay_registry::OverlayRegistry;
+use overlay_settings::{load_overlay_file, OverlaySettings};
+use performance_hud::PerformanceStats;
+use playback_controls::PlaybackControls;
+use redaction::{RedactionOptions, RedactionStyle};
+use roi_motion::RoiMotion;
 use side_menu::SideMenu;
-use video_face_tracker::{TrackerOptions, VideoFaceInfo, VideoFaceTracker};
+use source_picker::SourcePicker;
+use still_image::{StillImage, StillImagePanel};
+use video_face_tracker::{
+    apply_video_constraints, enumerate_cameras, open_source, play_video, CameraDevice,
+    CameraSelection, ConstraintRange, RoiGrowthMode, RoiGrowthOptions, TrackerOptions,
+    VideoFaceTracker, VideoOptions, VideoSource,
+};
 
 use leptos::*;
 use std::cell::{Cell, RefCell};
 use std::rc::Rc;
-use wasm_bindgen::UnwrapThrowExt;
 
 use std::ops::Deref;
 
-fn get_video_canvas_size(
-    header_height: u32,
-    video_element: &web_sys::HtmlVideoElement,
-) -> ((f64, f64), f64) {
-    let (video_width, video_height) = (video_element.video_width(), video_element.video_height());
-    let video_width_over_height = (video_width as f64) / (video_height as f64); //  ビデオ幅 / ビデオ高さ
-
-    let (screen_width, screen_height) = (
+/// ヘッダーを除いた表示できる領域のサイズ
+fn get_screen_size(header_height: u32) -> (f64, f64) {
+    (
         window()
             .inner_width()
             .expect("Cannot Get inner_width")
@@ -34,12 +62,22 @@ fn get_video_canvas_size(
             .inner_height()
             .expect("Cannot inner_height")
             .as_f64()
-            .expect("Sanity Check"),
-    );
+            .expect("Sanity Check")
+            - (header_height as f64),
+    )
+}
+
+fn get_video_canvas_size(
+    header_height: u32,
+    (video_width, video_height): (u32, u32),
+) -> (f64, f64) {
+    let video_width_over_height = (video_width as f64) / (video_height as f64); //  ビデオ幅 / ビデオ高さ
+
+    let (screen_width, screen_height) = get_screen_size(header_height);
 
     let (canvas_app_width, canvas_app_height) = if video_width > video_height {
         // ビデオ幅 > ビデオ高さの場合 -> 高さを合わせる
-        let canvas_app_height = screen_height - (header_height as f64);
+        let canvas_app_height = screen_height;
         let canvas_app_width = canvas_app_height * video_width_over_height;
         (canvas_app_width, canvas_app_height)
     } else {
@@ -49,44 +87,304 @@ fn get_video_canvas_size(
         (canvas_app_width, canvas_app_height)
     };
 
-    let canvas_app_over_video_scale = canvas_app_width / (video_width as f64);
+    (canvas_app_width, canvas_app_height)
+}
 
-    (
-        (canvas_app_width, canvas_app_height),
-        canvas_app_over_video_scale,
-    )
+/// インターバル内で利用する表示サイズ
+#[derive(Clone, Copy, Debug, Default)]
+struct CanvasLayout {
+    width: f64,
+    height: f64,
+    /// 入力画像の座標から表示の座標への変換(スケールと左右反転)
+    input_to_canvas: Transform,
+}
+
+impl CanvasLayout {
+    /// 入力画像の座標のroiから笑い男の状態を作成
+    fn laughing_man_state(&self, id: u32, rect: &Rect) -> LaughingManState {
+        LaughingManState {
+            id,
+            rect: self.input_to_canvas.apply_rect(rect),
+        }
+    }
+}
+
+/// 表示するサイズ．containの場合は内容の縦横比に合わせ，それ以外は表示できる領域全体を使う
+fn get_canvas_app_size(
+    header_height: u32,
+    content_size: (u32, u32),
+    object_fit: ObjectFit,
+) -> (f64, f64) {
+    match object_fit {
+        ObjectFit::Contain => get_video_canvas_size(header_height, content_size),
+        ObjectFit::Fill | ObjectFit::Cover => get_screen_size(header_height),
+    }
+}
+
+/// 入力画像を表示のサイズにobject-fitで配置し，必要なら左右反転する変換
+fn input_to_canvas_transform(
+    image_size: (u32, u32),
+    canvas_size: Size,
+    object_fit: ObjectFit,
+    is_mirrored: bool,
+) -> Transform {
+    let transform = Transform::fit(image_size.into(), canvas_size, object_fit);
+    if is_mirrored {
+        transform.then(&Transform::mirror_x(canvas_size.width))
+    } else {
+        transform
+    }
+}
+
+/// キャンバスのコンテキストの設定．キャンバスのサイズを変更すると初期化されるため，その度に呼ぶ
+fn initialize_canvas_context(canvas_context: &web_sys::CanvasRenderingContext2d) {
+    canvas_context.set_font("20px serif");
+    canvas_context.set_fill_style(&wasm_bindgen::JsValue::from_str("#FF0000"));
+    canvas_context.set_stroke_style(&wasm_bindgen::JsValue::from_str("#FF0000"));
+}
+
+/// スクリーンとビデオのサイズから各要素の高さと幅を設定する
+fn layout_canvas_app(
+    header_height: u32,
+    video_element: &web_sys::HtmlVideoElement,
+    canvas_element: &web_sys::HtmlCanvasElement,
+    image_size: (u32, u32),
+    object_fit: ObjectFit,
+    is_mirrored: bool,
+) -> Result<CanvasLayout, AppError> {
+    let (canvas_app_width, canvas_app_height) = get_canvas_app_size(
+        header_height,
+        (video_element.video_width(), video_element.video_height()),
+        object_fit,
+    );
+
+    video_element.set_width(canvas_app_width as u32);
+    video_element.set_height(canvas_app_height as u32);
+    // 表示の座標変換と同じ配置にする
+    video_element
+        .style()
+        .set_property("object-fit", object_fit.as_css())?;
+
+    canvas_element.set_width(canvas_app_width as u32);
+    canvas_element.set_height(canvas_app_height as u32);
+
+    initialize_canvas_context(&context2d(canvas_element)?);
+
+    Ok(CanvasLayout {
+        width: canvas_app_width,
+        height: canvas_app_height,
+        input_to_canvas: input_to_canvas_transform(
+            image_size,
+            Size::new(canvas_app_width, canvas_app_height),
+            object_fit,
+            is_mirrored,
+        ),
+    })
+}
+
+/// 静止画のサイズに合わせてキャンバスの高さと幅を設定する
+fn layout_still_image(
+    header_height: u32,
+    canvas_element: &web_sys::HtmlCanvasElement,
+    image_size: (u32, u32),
+    object_fit: ObjectFit,
+    is_mirrored: bool,
+) -> Result<CanvasLayout, AppError> {
+    let (canvas_app_width, canvas_app_height) =
+        get_canvas_app_size(header_height, image_size, object_fit);
+
+    canvas_element.set_width(canvas_app_width as u32);
+    canvas_element.set_height(canvas_app_height as u32);
+
+    initialize_canvas_context(&context2d(canvas_element)?);
+
+    Ok(CanvasLayout {
+        width: canvas_app_width,
+        height: canvas_app_height,
+        input_to_canvas: input_to_canvas_transform(
+            image_size,
+            Size::new(canvas_app_width, canvas_app_height),
+            object_fit,
+            is_mirrored,
+        ),
+    })
+}
+
+/// トラッカー(検出器)の準備ができていない場合のエラー
+fn tracker_not_ready() -> AppError {
+    AppError::OtherError("VideoFaceTracker is not ready.".to_string())
+}
+
+/// 入力ソースを切り替える．検出器(モデル)は読み込み直さない
+async fn switch_source(
+    video_face_tracker: Rc<RefCell<Option<VideoFaceTracker>>>,
+    source: VideoSource,
+) -> Result<(), AppError> {
+    let video_options = match video_face_tracker.borrow().as_ref() {
+        Some(video_face_tracker) => video_face_tracker.video_options().clone(),
+        None => return Err(tracker_not_ready()),
+    };
+    let source_stream = open_source(&source, &video_options).await?;
+
+    let stream_video = match video_face_tracker.borrow_mut().as_mut() {
+        Some(video_face_tracker) => {
+            video_face_tracker.replace_source(source, source_stream);
+            video_face_tracker.stream_video().clone()
+        }
+        None => return Err(tracker_not_ready()),
+    };
+
+    // 再生が始まってからサイズを合わせる
+    play_video(&stream_video).await?;
+
+    if let Some(video_face_tracker) = video_face_tracker.borrow_mut().as_mut() {
+        video_face_tracker.fit_to_video()?;
+    }
+    Ok(())
+}
+
+/// 一時停止したトラッカーを再開する．カメラの場合はストリームを取得し直す
+async fn resume_tracker(
+    video_face_tracker: Rc<RefCell<Option<VideoFaceTracker>>>,
+) -> Result<(), AppError> {
+    let reopen = match video_face_tracker.borrow().as_ref() {
+        Some(video_face_tracker) => video_face_tracker.needs_reopen().then(|| {
+            (
+                video_face_tracker.source().clone(),
+                video_face_tracker.video_options().clone(),
+            )
+        }),
+        None => return Ok(()),
+    };
+    let source_stream = match reopen {
+        Some((source, video_options)) => Some(open_source(&source, &video_options).await?),
+        None => None,
+    };
+
+    let stream_video = match video_face_tracker.borrow_mut().as_mut() {
+        Some(video_face_tracker) => {
+            video_face_tracker.resume(source_stream);
+            video_face_tracker.stream_video().clone()
+        }
+        None => return Ok(()),
+    };
+
+    play_video(&stream_video).await?;
+
+    // 取得し直したストリームのサイズが変わる場合があるため合わせる
+    if let Some(video_face_tracker) = video_face_tracker.borrow_mut().as_mut() {
+        video_face_tracker.fit_to_video()?;
+    }
+    Ok(())
+}
+
+/// 再生中のストリームに解像度やフレームレートの制約を適用する．
+/// 制約を適用できた場合のみオプションを保存する(ブラウザに拒否された場合は以前のオプションのまま)
+async fn apply_video_options(
+    video_face_tracker: Rc<RefCell<Option<VideoFaceTracker>>>,
+    video_options: VideoOptions,
+) -> Result<(), AppError> {
+    let (media_stream, camera_selection) = match video_face_tracker.borrow_mut().as_mut() {
+        Some(video_face_tracker) => {
+            match (
+                video_face_tracker.media_stream(),
+                video_face_tracker.source(),
+            ) {
+                (Some(media_stream), VideoSource::Camera(camera_selection)) => {
+                    (media_stream.clone(), camera_selection.clone())
+                }
+                // カメラ以外のソースには適用せず，次にカメラを開く時に使う
+                _ => {
+                    video_face_tracker.set_video_options(video_options);
+                    return Ok(());
+                }
+            }
+        }
+        None => return Err(tracker_not_ready()),
+    };
+
+    apply_video_constraints(&media_stream, &camera_selection, &video_options).await?;
+
+    if let Some(video_face_tracker) = video_face_tracker.borrow_mut().as_mut() {
+        video_face_tracker.set_video_options(video_options);
+        video_face_tracker.fit_to_video()?;
+    }
+    Ok(())
 }
 
 #[derive(Clone, Debug)]
 pub struct CanvasAppOptions {
+    pub video_options: VideoOptions,
     pub tracker_options: TrackerOptions,
     pub laughing_man_options: LaughingManOptions,
-    pub is_active_laughing_man: bool,
+    /// 顔の匿名化の方法
+    pub redaction_options: RedactionOptions,
+    /// 左右反転(セルフィー)表示
+    pub is_mirrored: bool,
+    /// 表示領域へのビデオの配置方法
+    pub object_fit: ObjectFit,
     pub interval_span: u32,
+    /// 重ねる画像(ユーザー画像)の設定
+    pub overlay_settings: OverlaySettings,
+    /// 追跡が不安定な時に映像全体を隠す機能
+    pub fail_safe_options: FailSafeOptions,
+    /// 枠を表示する場合に描画する層
+    pub debug_view_options: DebugViewOptions,
+    /// 検出の処理時間などのパネルを表示する
+    pub show_performance_hud: bool,
 }
 
 impl Default for CanvasAppOptions {
     fn default() -> Self {
         Self {
+            video_options: Default::default(),
             tracker_options: Default::default(),
             laughing_man_options: Default::default(),
-            is_active_laughing_man: true,
+            redaction_options: Default::default(),
+            is_mirrored: false,
+            object_fit: Default::default(),
             interval_span: 100,
+            overlay_settings: Default::default(),
+            fail_safe_options: Default::default(),
+            debug_view_options: Default::default(),
+            show_performance_hud: false,
         }
     }
 }
 
 #[component]
 pub fn CanvasApp(cx: Scope, header_height: u32) -> impl IntoView {
+    // 重ねる画像の設定は保存したものを使う
+    let initial_canvas_app_options = CanvasAppOptions {
+        overlay_settings: OverlaySettings::load(),
+        ..Default::default()
+    };
     let CanvasAppOptions {
+        video_options,
         tracker_options,
         laughing_man_options,
-        is_active_laughing_man,
+        redaction_options,
+        is_mirrored,
+        object_fit,
         interval_span,
-    } = Default::default();
+        overlay_settings,
+        fail_safe_options,
+        debug_view_options,
+        show_performance_hud,
+    } = initial_canvas_app_options.clone();
 
     // 笑い男画像の状態
-    let laughing_man_images = Rc::new(RefCell::new(Vec::<LaughingManImage>::new()));
+    let laughing_man_images = Rc::new(RefCell::new(OverlayRegistry::<LaughingManImage>::default()));
+    // 重ねる画像の集合(読み込み前は空)．DOMの場合も画像のサイズに使う
+    let laughing_man_renderers = Rc::new(RefCell::new(Vec::<LaughingManCanvasRenderer>::new()));
+    // トラックごとに割り当てた画像
+    let overlay_assigner = Rc::new(RefCell::new(OverlayAssigner::default()));
+    // トラッカーの更新の記録(描画ループで外挿する)
+    let roi_motion = Rc::new(RefCell::new(RoiMotion::default()));
+    // 映像全体を隠すかどうか(トラッカーの初期化時は安定した検出があるまで隠す)
+    let fail_safe = Rc::new(Cell::new(FailSafe::default()));
+    // 検出の処理時間などの統計
+    let performance_stats = Rc::new(RefCell::new(PerformanceStats::default()));
 
     // node_ref
     let container_node_ref = create_node_ref::<leptos::html::Div>(cx);
@@ -100,14 +398,49 @@ pub fn CanvasApp(cx: Scope, header_height: u32) -> impl IntoView {
     let (canvas_app_width, set_canvas_app_width) = create_signal(cx, 0 as u32);
     let (canvas_app_height, set_canvas_app_height) = create_signal(cx, 0 as u32);
 
+    // 表示サイズ(インターバル内で利用)
+    let canvas_layout = Rc::new(Cell::new(CanvasLayout::default()));
+
+    // 利用可能なカメラ
+    let (cameras, set_cameras) = create_signal(cx, Vec::<CameraDevice>::new());
+
+    // 静止画モードの状態
+    let still_image = Rc::new(RefCell::new(Option::<StillImage>::None));
+    let (is_still_image_mode, set_is_still_image_mode) = create_signal(cx, false);
+    let still_image_selected = create_rw_signal(cx, Vec::<bool>::new());
+    let still_image_export_mirrored = create_rw_signal(cx, false);
+
+    // トラッカーを一時停止しているかどうか
+    let (is_tracker_paused, set_is_tracker_paused) = create_signal(cx, false);
+    // 適用できなかった場合にサイドメニューに戻すストリームのオプション
+    let (restored_video_options, set_restored_video_options) =
+        create_signal(cx, Option::<VideoOptions>::None);
+
+    // 画面共有の停止などでソースが終了したかどうか
+    let (is_source_ended, set_is_source_ended) = create_signal(cx, false);
+
+    // 動画ファイルの再生状態
+    let (is_seekable, set_is_seekable) = create_signal(cx, false);
+    let (is_paused, set_is_paused) = create_signal(cx, true);
+    let (current_time, set_current_time) = create_signal(cx, 0.0_f64);
+    let (duration, set_duration) = create_signal(cx, 0.0_f64);
+
     // 各種オプション
     let tracker_options = Rc::new(RefCell::new(tracker_options));
     let laughing_man_options = Rc::new(RefCell::new(laughing_man_options)); // インターバル内で利用
-    let is_active_laughing_man = Rc::new(Cell::new(is_active_laughing_man)); // インターバル内で利用
+    let redaction_options = Rc::new(Cell::new(redaction_options)); // インターバル内で利用
     let interval_span = Rc::new(Cell::new(interval_span));
+    let overlay_settings = Rc::new(RefCell::new(overlay_settings));
+    let fail_safe_options = Rc::new(Cell::new(fail_safe_options)); // インターバル内で利用
+    let debug_view_options = Rc::new(Cell::new(debug_view_options)); // インターバル内で利用
+    let show_performance_hud = Rc::new(Cell::new(show_performance_hud)); // インターバル内で利用
+    let (is_mirrored, set_is_mirrored) = create_signal(cx, is_mirrored);
+    let (object_fit, set_object_fit) = create_signal(cx, object_fit);
 
     // インターバルのLAII
     let interval_state = Rc::new(RefCell::new(Option::<Interval>::None));
+    // 描画ループのLAII
+    let render_loop_state = Rc::new(RefCell::new(Option::<AnimationFrameLoop>::None));
 
     // サイドメニューが開いているかどうか
     let is_side_menu_active = use_context::<IsSideMenuActive>(cx).expect("Cannot get context");
@@ -119,6 +452,113 @@ pub fn CanvasApp(cx: Scope, header_height: u32) -> impl IntoView {
         }
     };
 
+    // 静止画モードのプレビューを描画
+    let redraw_still_image = {
+        let still_image = still_image.clone();
+        let canvas_layout = canvas_layout.clone();
+        let laughing_man_options = laughing_man_options.clone();
+        let redaction_options = redaction_options.clone();
+
+        move || {
+            let Some(canvas_element) = canvas_node_ref.get() else {
+                return;
+            };
+            if let Some(still_image) = still_image.borrow().as_ref() {
+                let CanvasLayout {
+                    width,
+                    height,
+                    input_to_canvas,
+                } = canvas_layout.get();
+                let draw_res = context2d(&canvas_element).and_then(|canvas_context| {
+                    canvas_context.clear_rect(0.0, 0.0, width, height);
+                    still_image.draw(
+                        &canvas_context,
+                        &input_to_canvas,
+                        &still_image_selected.get_untracked(),
+                        &laughing_man_options.borrow(),
+                        &redaction_options.get(),
+                        true,
+                    )
+                });
+                if let Err(e) = draw_res {
+                    log::error!("{e}");
+                }
+            }
+        }
+    };
+
+    // ビデオ(静止画モードの場合は静止画)のサイズに合わせて各要素のサイズを再設定．
+    // 表示中の笑い男画像もトラッキング結果を維持したまま新しいレイアウトに合わせる
+    let relayout = {
+        let video_face_tracker = video_face_tracker.clone();
+        let canvas_layout = canvas_layout.clone();
+        let still_image = still_image.clone();
+        let redraw_still_image = redraw_still_image.clone();
+        let laughing_man_images = laughing_man_images.clone();
+
+        move || {
+            let Some(canvas_element) = canvas_node_ref.get() else {
+                return;
+            };
+            let layout_res = if let Some(still_image) = still_image.borrow().as_ref() {
+                Some(layout_still_image(
+                    header_height,
+                    &canvas_element,
+                    still_image.size(),
+                    object_fit.get_untracked(),
+                    is_mirrored.get_untracked(),
+                ))
+            } else {
+                video_face_tracker
+                    .borrow()
+                    .as_ref()
+                    .map(|video_face_tracker| {
+                        layout_canvas_app(
+                            header_height,
+                            video_face_tracker.stream_video(),
+                            &canvas_element,
+                            video_face_tracker.image_size(),
+                            object_fit.get_untracked(),
+                            is_mirrored.get_untracked(),
+                        )
+                    })
+            };
+            match layout_res {
+                Some(Ok(new_canvas_layout)) => {
+                    set_canvas_app_width.set(new_canvas_layout.width as u32);
+                    set_canvas_app_height.set(new_canvas_layout.height as u32);
+                    canvas_layout.set(new_canvas_layout);
+                    redraw_still_image();
+
+                    if let Some(video_face_tracker) = video_face_tracker.borrow().as_ref() {
+                        let laughing_man_images = laughing_man_images.borrow();
+                        let roi_growth = video_face_tracker.roi_growth();
+                        for roi in video_face_tracker.rois() {
+                            if let Some(laughing_man_img) = laughing_man_images.get(roi.id) {
+                                let state = new_canvas_layout
+                                    .laughing_man_state(roi.id, &roi.cover_rect(&roi_growth));
+                                if let Err(e) = laughing_man_img.step(state) {
+                                    log::error!("{e}");
+                                }
+                            }
+                        }
+                    }
+                }
+                Some(Err(e)) => log::error!("{e}"),
+                None => {}
+            }
+        }
+    };
+
+    // 確認用の選択が変わったらプレビューを再描画
+    create_effect(cx, {
+        let redraw_still_image = redraw_still_image.clone();
+        move |_| {
+            still_image_selected.with(|_| ());
+            redraw_still_image();
+        }
+    });
+
     // データの読み込みとカメラの許可の文章
     let (show_info, set_show_info) = create_signal(
         cx,
@@ -130,24 +570,56 @@ pub fn CanvasApp(cx: Scope, header_height: u32) -> impl IntoView {
         // 他の場所で使う場合はここでクローン
         // 状態
         let laughing_man_images = laughing_man_images.clone();
+        let overlay_assigner = overlay_assigner.clone();
+        let roi_motion = roi_motion.clone();
+        let fail_safe = fail_safe.clone();
+        let performance_stats = performance_stats.clone();
         let video_face_tracker = video_face_tracker.clone();
         // インターバルハンドル
         let interval_state = interval_state.clone();
+        let render_loop_state = render_loop_state.clone();
         // オプション
         let laughing_man_options = laughing_man_options.clone();
-        let is_active_laughing_man = is_active_laughing_man.clone();
+        let redaction_options = redaction_options.clone();
+        let fail_safe_options = fail_safe_options.clone();
+        let debug_view_options = debug_view_options.clone();
+        let show_performance_hud = show_performance_hud.clone();
+        let interval_span = interval_span.clone();
+        // レイアウト
+        let canvas_layout = canvas_layout.clone();
+        let relayout = relayout.clone();
+        // 静止画モード
+        let still_image = still_image.clone();
+        // キャンバスの笑い男画像
+        let laughing_man_renderers = laughing_man_renderers.clone();
+        let overlay_settings = overlay_settings.clone();
 
         move |_| {
             log::info!("VideoFaceTracker initialize.");
             // 状態
             let laughing_man_images = laughing_man_images.clone();
+            let overlay_assigner = overlay_assigner.clone();
+            let roi_motion = roi_motion.clone();
+            let fail_safe = fail_safe.clone();
+            let performance_stats = performance_stats.clone();
+            let laughing_man_renderers = laughing_man_renderers.clone();
+            let overlay_settings = overlay_settings.clone();
             let video_face_tracker = video_face_tracker.clone();
+            let still_image = still_image.clone();
+            // レイアウト
+            let canvas_layout = canvas_layout.clone();
+            let relayout = relayout.clone();
             // インターバルハンドル
             let interval_state = interval_state.clone();
+            let render_loop_state = render_loop_state.clone();
             // オプション
+            let video_options = video_options.clone();
             let tracker_options = tracker_options.clone();
             let laughing_man_options = laughing_man_options.clone();
-            let is_active_laughing_man = is_active_laughing_man.clone();
+            let redaction_options = redaction_options.clone();
+            let fail_safe_options = fail_safe_options.clone();
+            let debug_view_options = debug_view_options.clone();
+            let show_performance_hud = show_performance_hud.clone();
             let interval_span = interval_span.clone();
             spawn_local(async move {
                 let video_element: web_sys::HtmlVideoElement =
@@ -164,13 +636,12 @@ pub fn CanvasApp(cx: Scope, header_height: u32) -> impl IntoView {
                     container_div.into()
                 };
 
-                let image_over_video_scale =
-                    { tracker_options.borrow_mut().image_over_video_scale }; // 画像 / ビデオ のスケール
-
                 let tracker_res = VideoFaceTracker::new(
-                    video_element.clone(),
+                    video_element,
                     "https://dl.dropboxusercontent.com/s/ypb7jrufzgghp62/seeta_fd_frontal_v1.0.bin"
                         .to_string(),
+                    VideoSource::default(),
+                    video_options,
                     tracker_options.replace_with(|options| options.clone()),
                 )
                 .await;
@@ -180,164 +651,92 @@ pub fn CanvasApp(cx: Scope, header_height: u32) -> impl IntoView {
                         {
                             *video_face_tracker.borrow_mut() = Some(tracker);
                         }
-                        // メッセージを初期化
-                        set_show_info.set(None);
+                        // メッセージを初期化．カメラを開けずに合成映像にした場合は知らせる
+                        let is_synthetic_fallback = video_face_tracker
+                            .borrow()
+                            .as_ref()
+                            .is_some_and(|tracker| tracker.source() == &VideoSource::Synthetic);
+                        set_show_info.set(is_synthetic_fallback.then(|| {
+                            "カメラを利用できないため合成映像を表示しています".to_string()
+                        }));
 
                         // スクリーンのサイズから表示するサイズを設定
+                        relayout();
 
-                        let ((canvas_app_width, canvas_app_height), canvas_app_over_video_scale) =
-                            get_video_canvas_size(header_height, &video_element);
-
-                        // 各種要素に高さと幅を設定
-                        video_element.set_width(canvas_app_width as u32);
-                        video_element.set_height(canvas_app_height as u32);
-
-                        canvas_element.set_width(canvas_app_width as u32);
-                        canvas_element.set_height(canvas_app_height as u32);
-
-                        set_canvas_app_width.set(canvas_app_width as u32);
-                        set_canvas_app_height.set(canvas_app_height as u32);
-
-                        // 表示画像 / 入力画像
-                        let canvas_app_over_input_image =
-                            (1.0 / image_over_video_scale) * canvas_app_over_video_scale;
+                        // 重ねる画像の読み込み．保存したユーザー画像が読み込めない場合は笑い男画像に戻す
+                        let layer_set = overlay_settings.borrow().layer_set();
+                        let renderers_res =
+                            match LaughingManCanvasRenderer::load_all(&layer_set).await {
+                                Ok(renderers) => Ok(renderers),
+                                Err(e) => {
+                                    log::error!("{e}");
+                                    *overlay_settings.borrow_mut() = OverlaySettings::default();
+                                    LaughingManCanvasRenderer::load_all(
+                                        &OverlaySettings::default().layer_set(),
+                                    )
+                                    .await
+                                }
+                            };
+                        match renderers_res {
+                            Ok(renderers) => *laughing_man_renderers.borrow_mut() = renderers,
+                            Err(e) => {
+                                log::error!("{e}");
+                                set_show_info.set(Some(
+                                    "重ねる画像を読み込めないため顔を塗りつぶしています。詳しくはコンソールを見て下さい"
+                                        .to_string(),
+                                ));
+                            }
+                        }
 
-                        // キャンバスのコンテキストの設定
-                        let canvas_context =
-                            context2d(&canvas_element).expect("Cannot get context2d.");
+                        // 利用可能なカメラの取得(許可後でないとラベルが取得できない)
+                        match enumerate_cameras().await {
+                            Ok(cameras) => set_cameras.set(cameras),
+                            Err(e) => log::error!("{e}"),
+                        }
 
-                        canvas_context.set_font("20px serif");
-                        canvas_context.set_fill_style(&wasm_bindgen::JsValue::from_str("#FF0000"));
-                        canvas_context
-                            .set_stroke_style(&wasm_bindgen::JsValue::from_str("#FF0000"));
+                        // インターバルと描画ループで共有する状態
+                        let frame_loop = FrameLoop {
+                            canvas_context: context2d(&canvas_element)
+                                .expect("Cannot get context2d."),
+                            container_node,
+                            video_face_tracker,
+                            still_image,
+                            canvas_layout,
+                            laughing_man_images,
+                            laughing_man_renderers,
+                            overlay_assigner,
+                            roi_motion,
+                            fail_safe,
+                            performance_stats,
+                            laughing_man_options,
+                            redaction_options,
+                            fail_safe_options,
+                            debug_view_options,
+                            show_performance_hud,
+                            interval_span,
+                            is_source_ended,
+                            set_is_source_ended,
+                            set_show_info,
+                            last_frame: Rc::new(RefCell::new(None)),
+                        };
 
                         // インターバルを設定
-                        let interval = Interval::new(interval_span.get(), {
-                            let video_face_tracker = video_face_tracker.clone();
-
-                            move || {
-                                if let Some(video_face_tracker) =
-                                    video_face_tracker.borrow_mut().as_mut()
-                                {
-                                    let VideoFaceInfo {
-                                        rois,
-                                        added_rois,
-                                        removed_rois,
-                                        span_time,
-                                    } = video_face_tracker
-                                        .step()
-                                        .expect("Cannot VideoFaceTracker step");
-
-                                    let roi_numbers = rois.len();
-
-                                    // キャンパスの初期化
-                                    canvas_context.clear_rect(
-                                        0.0,
-                                        0.0,
-                                        canvas_app_width,
-                                        canvas_app_height,
-                                    );
-
-                                    // domの追加
-                                    if is_active_laughing_man.get() {
-                                        // 笑い男モードの場合
-                                        for roi in added_rois.into_iter() {
-                                            let state = LaughingManState {
-                                                id: roi.id,
-                                                left: (roi.tl_x * canvas_app_over_input_image)
-                                                    as u32,
-                                                top: (roi.tl_y * canvas_app_over_input_image)
-                                                    as u32,
-                                                width: (roi.width * canvas_app_over_input_image)
-                                                    as u32,
-                                                height: (roi.height * canvas_app_over_input_image)
-                                                    as u32,
-                                            };
-
-                                            let laughing_man_img = LaughingManImage::new(
-                                                container_node.clone(),
-                                                "/laughing-man.svg",
-                                                laughing_man_options
-                                                    .replace_with(|options| options.clone()),
-                                                state,
-                                            )
-                                            .expect("Cannot create laughing_man_img");
-
-                                            {
-                                                laughing_man_images
-                                                    .borrow_mut()
-                                                    .push(laughing_man_img)
-                                            }
-                                        }
-                                    }
-
-                                    // domの削除
-                                    if is_active_laughing_man.get() {
-                                        // 笑い男モードの場合
-                                        laughing_man_images.borrow_mut().retain(
-                                            |laughing_man_img| {
-                                                !removed_rois
-                                                    .iter()
-                                                    .any(|roi| laughing_man_img.id() == roi.id)
-                                            },
-                                        )
-                                    }
-
-                                    // 笑い男の遷移(描画)
-                                    if is_active_laughing_man.get() {
-                                        // 笑い男モードの場合
-                                        for (roi, laughing_man_img) in rois
-                                            .into_iter()
-                                            .zip(laughing_man_images.borrow_mut().iter_mut())
-                                        {
-                                            let state = LaughingManState {
-                                                id: roi.id,
-                                                left: (roi.tl_x * canvas_app_over_input_image)
-                                                    as u32,
-                                                top: (roi.tl_y * canvas_app_over_input_image)
-                                                    as u32,
-                                                width: (roi.width * canvas_app_over_input_image)
-                                                    as u32,
-                                                height: (roi.height * canvas_app_over_input_image)
-                                                    as u32,
-                                            };
-
-                                            laughing_man_img.step(state).unwrap_throw();
-                                        }
-                                    } else {
-                                        // 笑い男モードでない場合
-                                        for roi in rois {
-                                            canvas_context.stroke_rect(
-                                                roi.tl_x * canvas_app_over_input_image,
-                                                roi.tl_y * canvas_app_over_input_image,
-                                                roi.width * canvas_app_over_input_image,
-                                                roi.height * canvas_app_over_input_image,
-                                            );
-
-                                            canvas_context
-                                                .fill_text(
-                                                    &format!("id: {}", roi.id),
-                                                    roi.tl_x * canvas_app_over_input_image,
-                                                    roi.tl_y * canvas_app_over_input_image,
-                                                )
-                                                .unwrap_throw();
-                                        }
-                                    }
-
-                                    let text_info = format!(
-                                        "{:03} faces detected in {:.1}[ms]",
-                                        roi_numbers, span_time
-                                    );
-                                    canvas_context
-                                        .fill_text(&text_info, 20.0, 20.0)
-                                        .expect("Cannot add text");
-                                }
-                            }
+                        let interval = Interval::new(frame_loop.interval_span.get(), {
+                            let frame_loop = frame_loop.clone();
+                            move || frame_loop.tick()
                         });
 
                         {
                             *interval_state.borrow_mut() = Some(interval);
                         }
+
+                        // 描画ループ．検出のインターバルとは別に，画面の更新ごとに笑い男画像と匿名化の位置を外挿する
+                        let render_loop =
+                            AnimationFrameLoop::new(move |time| frame_loop.animate(time));
+                        match render_loop {
+                            Ok(render_loop) => *render_loop_state.borrow_mut() = Some(render_loop),
+                            Err(e) => log::error!("{e}"),
+                        }
                     }
                     Err(e) => {
                         log::error!("{e}");
@@ -355,38 +754,159 @@ pub fn CanvasApp(cx: Scope, header_height: u32) -> impl IntoView {
     let on_apply = {
         // 状態
         let laughing_man_images = laughing_man_images.clone();
+        let overlay_assigner = overlay_assigner.clone();
         let video_face_tracker = video_face_tracker.clone();
         // インターバルハンドル
         let interval_state = interval_state.clone();
         // オプション
         let laughing_man_options = laughing_man_options.clone();
-        let is_active_laughing_man = is_active_laughing_man.clone();
+        let redaction_options = redaction_options.clone();
+        let overlay_settings = overlay_settings.clone();
+        let fail_safe_options = fail_safe_options.clone();
+        let debug_view_options = debug_view_options.clone();
+        let show_performance_hud = show_performance_hud.clone();
+        let interval_span = interval_span.clone();
+        let fail_safe = fail_safe.clone();
+        let performance_stats = performance_stats.clone();
+        // キャンバスの笑い男画像
+        let laughing_man_renderers = laughing_man_renderers.clone();
+        // レイアウト
+        let relayout = relayout.clone();
 
         move |new_canvas_app_options| {
             let CanvasAppOptions {
+                video_options: new_video_options,
                 tracker_options: new_tracker_options,
                 laughing_man_options: new_laughing_man_options,
-                is_active_laughing_man: new_is_active_laughing_man,
+                redaction_options: new_redaction_options,
+                is_mirrored: new_is_mirrored,
+                object_fit: new_object_fit,
                 interval_span: new_interval_span,
+                overlay_settings: new_overlay_settings,
+                fail_safe_options: new_fail_safe_options,
+                debug_view_options: new_debug_view_options,
+                show_performance_hud: new_show_performance_hud,
             } = new_canvas_app_options;
 
+            // 左右反転と配置方法はレイアウトに反映する
+            set_is_mirrored.set(new_is_mirrored);
+            set_object_fit.set(new_object_fit);
+
             // トラッカーの初期化
             {
                 if let Some(video_face_tracker) = video_face_tracker.borrow_mut().as_mut() {
-                    if let Err(e) = video_face_tracker.initialize_tracker(new_tracker_options) {
+                    if let Err(e) =
+                        video_face_tracker.initialize_tracker(new_tracker_options.clone())
+                    {
                         log::error!("{e}");
                     }
                 }
             }
             // 笑い男画像の初期化
             {
-                *laughing_man_images.borrow_mut() = Vec::new();
+                laughing_man_images.borrow_mut().clear();
+            }
+            overlay_assigner.borrow_mut().clear();
+            fail_safe.set(FailSafe::default());
+            *performance_stats.borrow_mut() = PerformanceStats::default();
+            // 入力画像のサイズが変わるため再レイアウト
+            relayout();
+
+            // ストリームのオプションが変わった場合は制約を適用
+            let previous_video_options = video_face_tracker
+                .borrow()
+                .as_ref()
+                .map(|video_face_tracker| video_face_tracker.video_options().clone())
+                .filter(|video_options| *video_options != new_video_options);
+            if let Some(previous_video_options) = previous_video_options {
+                let video_face_tracker = video_face_tracker.clone();
+                let laughing_man_images = laughing_man_images.clone();
+                let overlay_assigner = overlay_assigner.clone();
+                let relayout = relayout.clone();
+
+                spawn_local(async move {
+                    match apply_video_options(video_face_tracker, new_video_options).await {
+                        Ok(_) => {
+                            {
+                                laughing_man_images.borrow_mut().clear();
+                            }
+                            overlay_assigner.borrow_mut().clear();
+                            relayout();
+                        }
+                        Err(e) => {
+                            // 適用できなかった場合はサイドメニューの値を以前のオプションに戻す
+                            log::error!("{e}");
+                            set_restored_video_options.set(Some(previous_video_options));
+                            set_show_info.set(Some(
+                                "解像度やフレームレートを適用できませんでした。以前の値に戻しました"
+                                    .to_string(),
+                            ));
+                        }
+                    }
+                });
+            }
+
+            // 重ねる画像が変わった場合は保存して読み込み直す．
+            // 読み込み中に追加されたトラックの画像が古いままにならないよう，読み込み後にトラッカーを初期化する
+            if *overlay_settings.borrow() != new_overlay_settings {
+                if let Err(e) = new_overlay_settings.save() {
+                    log::error!("{e}");
+                }
+                let layer_set = new_overlay_settings.layer_set();
+                {
+                    *overlay_settings.borrow_mut() = new_overlay_settings;
+                }
+
+                let video_face_tracker = video_face_tracker.clone();
+                let laughing_man_images = laughing_man_images.clone();
+                let overlay_assigner = overlay_assigner.clone();
+                let laughing_man_renderers = laughing_man_renderers.clone();
+                let new_tracker_options = new_tracker_options.clone();
+                spawn_local(async move {
+                    match LaughingManCanvasRenderer::load_all(&layer_set).await {
+                        Ok(renderers) => {
+                            {
+                                *laughing_man_renderers.borrow_mut() = renderers;
+                            }
+                            {
+                                laughing_man_images.borrow_mut().clear();
+                            }
+                            overlay_assigner.borrow_mut().clear();
+                            if let Some(video_face_tracker) =
+                                video_face_tracker.borrow_mut().as_mut()
+                            {
+                                if let Err(e) =
+                                    video_face_tracker.initialize_tracker(new_tracker_options)
+                                {
+                                    log::error!("{e}");
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            log::error!("{e}");
+                            set_show_info.set(Some(
+                                "重ねる画像を読み込めませんでした。以前の画像を使います"
+                                    .to_string(),
+                            ));
+                        }
+                    }
+                });
             }
 
             // インターバル内で利用する状態の更新
             {
                 *laughing_man_options.borrow_mut() = new_laughing_man_options;
-                is_active_laughing_man.set(new_is_active_laughing_man);
+                redaction_options.set(new_redaction_options);
+                // 無効から有効にした場合は，それまでの状態によらず安定した検出があるまで隠す
+                if fail_safe_options.get().mode == FailSafeMode::Off
+                    && new_fail_safe_options.mode != FailSafeMode::Off
+                {
+                    fail_safe.set(FailSafe::default());
+                }
+                fail_safe_options.set(new_fail_safe_options);
+                debug_view_options.set(new_debug_view_options);
+                show_performance_hud.set(new_show_performance_hud);
+                interval_span.set(new_interval_span);
             }
 
             // インターバルハンドルの初期化
@@ -402,19 +922,381 @@ pub fn CanvasApp(cx: Scope, header_height: u32) -> impl IntoView {
         }
     };
 
+    // 入力ソースの選択時の処理
+    let on_select_source = {
+        let video_face_tracker = video_face_tracker.clone();
+        let laughing_man_images = laughing_man_images.clone();
+        let overlay_assigner = overlay_assigner.clone();
+        let fail_safe = fail_safe.clone();
+        let performance_stats = performance_stats.clone();
+
+        let still_image = still_image.clone();
+        let relayout = relayout.clone();
+
+        move |source: VideoSource| {
+            let video_face_tracker = video_face_tracker.clone();
+            let laughing_man_images = laughing_man_images.clone();
+            let overlay_assigner = overlay_assigner.clone();
+            let fail_safe = fail_safe.clone();
+            let performance_stats = performance_stats.clone();
+            let relayout = relayout.clone();
+            let is_seekable = source.is_seekable();
+
+            // 静止画モードを終了
+            {
+                *still_image.borrow_mut() = None;
+            }
+            set_is_still_image_mode.set(false);
+
+            spawn_local(async move {
+                match switch_source(video_face_tracker, source).await {
+                    Ok(_) => {
+                        // トラッカーが初期化されるため笑い男画像も初期化
+                        {
+                            laughing_man_images.borrow_mut().clear();
+                        }
+                        overlay_assigner.borrow_mut().clear();
+                        fail_safe.set(FailSafe::default());
+                        *performance_stats.borrow_mut() = PerformanceStats::default();
+                        set_is_source_ended.set(false);
+                        set_is_tracker_paused.set(false);
+                        set_show_info.set(None);
+                        set_is_seekable.set(is_seekable);
+                        relayout();
+                    }
+                    Err(e) => {
+                        log::error!("{e}");
+                        set_show_info.set(Some(
+                            "入力ソースを開けませんでした。詳しくはコンソールを見て下さい"
+                                .to_string(),
+                        ));
+                    }
+                }
+            });
+        }
+    };
+
+    // 静止画の選択時の処理
+    let on_select_image = {
+        let video_face_tracker = video_face_tracker.clone();
+        let laughing_man_images = laughing_man_images.clone();
+        let still_image = still_image.clone();
+        let overlay_settings = overlay_settings.clone();
+        let laughing_man_options = laughing_man_options.clone();
+        let relayout = relayout.clone();
+
+        move |object_url: String| {
+            let video_face_tracker = video_face_tracker.clone();
+            let laughing_man_images = laughing_man_images.clone();
+            let still_image = still_image.clone();
+            let layer_set = overlay_settings.borrow().layer_set();
+            let assignment = laughing_man_options.borrow().assignment;
+            let relayout = relayout.clone();
+
+            spawn_local(async move {
+                let load_res = StillImage::load(object_url, &layer_set).await.and_then(
+                    |mut new_still_image| {
+                        // 検出しないまま保存できると匿名化されていない画像になるため，検出器が無い場合は読み込まない
+                        let mut video_face_tracker = video_face_tracker.borrow_mut();
+                        let video_face_tracker =
+                            video_face_tracker.as_mut().ok_or_else(tracker_not_ready)?;
+                        let (faces, landmarks) =
+                            video_face_tracker.detect_image(new_still_image.image())?;
+                        new_still_image.set_faces(faces, landmarks, assignment);
+                        // 静止画モードの間はライブの映像を止める
+                        video_face_tracker.pause();
+                        Ok(new_still_image)
+                    },
+                );
+
+                match load_res {
+                    Ok(new_still_image) => {
+                        let face_count = new_still_image.faces().len();
+                        {
+                            *still_image.borrow_mut() = Some(new_still_image);
+                        }
+                        // ビデオの笑い男画像は表示しない
+                        {
+                            laughing_man_images.borrow_mut().clear();
+                        }
+                        set_is_still_image_mode.set(true);
+                        set_is_tracker_paused.set(true);
+                        set_show_info.set(None);
+                        relayout();
+                        still_image_selected.set(vec![true; face_count]);
+                    }
+                    Err(e) => {
+                        log::error!("{e}");
+                        set_show_info.set(Some(
+                            "検出器の準備ができていないか画像を読み込めませんでした。詳しくはコンソールを見て下さい"
+                                .to_string(),
+                        ));
+                    }
+                }
+            });
+        }
+    };
+
+    // 静止画のダウンロード時の処理
+    let on_download_still_image = {
+        let still_image = still_image.clone();
+        let laughing_man_options = laughing_man_options.clone();
+        let redaction_options = redaction_options.clone();
+
+        move || {
+            if let Some(still_image) = still_image.borrow().as_ref() {
+                if let Err(e) = still_image.download_png(
+                    still_image_export_mirrored.get_untracked(),
+                    &still_image_selected.get_untracked(),
+                    &laughing_man_options.borrow(),
+                    &redaction_options.get(),
+                    "laughing-man.png",
+                ) {
+                    log::error!("{e}");
+                }
+            }
+        }
+    };
+
+    // 一時停止・再開ボタンの処理
+    let toggle_tracker_pause = {
+        let video_face_tracker = video_face_tracker.clone();
+        let still_image = still_image.clone();
+        let laughing_man_images = laughing_man_images.clone();
+        let overlay_assigner = overlay_assigner.clone();
+        let fail_safe = fail_safe.clone();
+        let relayout = relayout.clone();
+
+        move |_| {
+            if !is_tracker_paused.get_untracked() {
+                if let Some(video_face_tracker) = video_face_tracker.borrow_mut().as_mut() {
+                    video_face_tracker.pause();
+                }
+                set_is_tracker_paused.set(true);
+                return;
+            }
+
+            let video_face_tracker = video_face_tracker.clone();
+            let laughing_man_images = laughing_man_images.clone();
+            let overlay_assigner = overlay_assigner.clone();
+            let fail_safe = fail_safe.clone();
+            let still_image = still_image.clone();
+            let relayout = relayout.clone();
+            spawn_local(async move {
+                match resume_tracker(video_face_tracker).await {
+                    Ok(_) => {
+                        // 再開した場合は静止画モードを終了する
+                        {
+                            *still_image.borrow_mut() = None;
+                        }
+                        set_is_still_image_mode.set(false);
+                        // トラッカーが初期化されるため笑い男画像も初期化
+                        {
+                            laughing_man_images.borrow_mut().clear();
+                        }
+                        overlay_assigner.borrow_mut().clear();
+                        fail_safe.set(FailSafe::default());
+                        set_is_tracker_paused.set(false);
+                        relayout();
+                    }
+                    Err(e) => log::error!("{e}"),
+                }
+            });
+        }
+    };
+
+    // ウインドウのサイズ変更や画面の回転に合わせて再レイアウト
+    let window_listeners = ["resize", "orientationchange"]
+        .into_iter()
+        .filter_map(|event_name| {
+            let relayout = relayout.clone();
+            EventListener::new(&window(), event_name, move |_| relayout())
+                .map_err(|e| log::error!("{e}"))
+                .ok()
+        })
+        .collect::<Vec<_>>();
+
+    // ビデオの解像度が変わった場合(カメラの回転など)はトラッキング結果を維持したまま合わせる
+    let on_video_resize = {
+        let video_face_tracker = video_face_tracker.clone();
+        let relayout = relayout.clone();
+        move |_| {
+            if let Some(video_face_tracker) = video_face_tracker.borrow_mut().as_mut() {
+                if let Err(e) = video_face_tracker.resize_to_video() {
+                    log::error!("{e}");
+                }
+            }
+            relayout();
+        }
+    };
+
+    // 手動で割り当てる場合は，クリックした顔の画像を次の画像に切り替える
+    let on_click_container = {
+        let video_face_tracker = video_face_tracker.clone();
+        let canvas_layout = canvas_layout.clone();
+        let still_image = still_image.clone();
+        let laughing_man_images = laughing_man_images.clone();
+        let laughing_man_renderers = laughing_man_renderers.clone();
+        let overlay_assigner = overlay_assigner.clone();
+        let laughing_man_options = laughing_man_options.clone();
+
+        move |ev: web_sys::MouseEvent| {
+            let options = laughing_man_options.borrow().clone();
+            if options.assignment != OverlayAssignment::Manual || still_image.borrow().is_some() {
+                return;
+            }
+            let Some(container_div) = container_node_ref.get() else {
+                return;
+            };
+            let bounding_rect = container_div.get_bounding_client_rect();
+            let (x, y) = (
+                ev.client_x() as f64 - bounding_rect.left(),
+                ev.client_y() as f64 - bounding_rect.top(),
+            );
+
+            let layout = canvas_layout.get();
+            let video_face_tracker = video_face_tracker.borrow();
+            let Some(video_face_tracker) = video_face_tracker.as_ref() else {
+                return;
+            };
+            // 重なっている場合は後(上)に描かれた顔を優先する
+            let roi_growth = video_face_tracker.roi_growth();
+            let Some(state) = video_face_tracker
+                .rois()
+                .into_iter()
+                .rev()
+                .map(|roi| layout.laughing_man_state(roi.id, &roi.cover_rect(&roi_growth)))
+                .find(|state| state.rect.contains(x, y))
+            else {
+                return;
+            };
+
+            let renderers = laughing_man_renderers.borrow();
+            let index = overlay_assigner
+                .borrow_mut()
+                .cycle(state.id, renderers.len());
+            // DOMの場合は画像を作り直す．キャンバスの場合は次の描画で反映される
+            let mut laughing_man_images = laughing_man_images.borrow_mut();
+            if let (Some(laughing_man_img), Some(renderer)) =
+                (laughing_man_images.get_mut(state.id), renderers.get(index))
+            {
+                let container_node: web_sys::Node = container_div.deref().clone().into();
+                match LaughingManImage::new(container_node, renderer, options, state) {
+                    Ok(new_laughing_man_img) => *laughing_man_img = new_laughing_man_img,
+                    Err(e) => log::error!("{e}"),
+                }
+            }
+        }
+    };
+
+    // コンポーネントの破棄時にインターバルを止めてカメラを解放する
+    on_cleanup(cx, {
+        let video_face_tracker = video_face_tracker.clone();
+        let interval_state = interval_state.clone();
+        let render_loop_state = render_loop_state.clone();
+        let laughing_man_images = laughing_man_images.clone();
+        move || {
+            drop(window_listeners);
+            {
+                *interval_state.borrow_mut() = None;
+                *render_loop_state.borrow_mut() = None;
+            }
+            {
+                laughing_man_images.borrow_mut().clear();
+            }
+            if let Some(mut video_face_tracker) = video_face_tracker.borrow_mut().take() {
+                video_face_tracker.shutdown();
+            }
+        }
+    });
+
+    // カメラの選択時の処理
+    let on_select_camera = {
+        let on_select_source = on_select_source.clone();
+        move |camera_selection: CameraSelection| {
+            on_select_source(VideoSource::Camera(camera_selection));
+        }
+    };
+
+    // 軌跡の保存時の処理
+    let on_export_trajectories = {
+        let video_face_tracker = video_face_tracker.clone();
+        move || {
+            if let Some(video_face_tracker) = video_face_tracker.borrow().as_ref() {
+                let href = format!(
+                    "data:text/csv;charset=utf-8,{}",
+                    js_sys::encode_uri_component(&video_face_tracker.trajectories_csv())
+                );
+                if let Err(e) = download_url(&href, "trajectories.csv") {
+                    log::error!("{e}");
+                }
+            }
+        }
+    };
+
     view! {cx,
         <div class=expand_menu_class>
-            <SideMenu canvas_app_options={Default::default()} on_apply=on_apply/>
+            <SideMenu
+                canvas_app_options=initial_canvas_app_options
+                cameras=cameras
+                on_apply=on_apply
+                on_select_camera=on_select_camera
+                on_export_trajectories=on_export_trajectories
+                restored_video_options=restored_video_options
+            />
         </div>
         <div
             class="canvas-app-container"
             style:width = move ||{format!("{}px", canvas_app_width.get())}
             style:height = move ||{format!("{}px", canvas_app_height.get())}
             node_ref=container_node_ref
+            on:click=on_click_container
         >
-            <video class="stream-video" node_ref=video_node_ref></video>
+            <video class="stream-video" node_ref=video_node_ref
+                style:visibility=move || {
+                    if is_still_image_mode.get() || is_source_ended.get() {"hidden"} else {"visible"}
+                }
+                style:transform=move || if is_mirrored.get() {"scaleX(-1)"} else {"none"}
+                on:resize=on_video_resize
+                on:play=move |_| set_is_paused.set(false)
+                on:pause=move |_| set_is_paused.set(true)
+                on:timeupdate=move |ev| {
+                    set_current_time.set(event_target::<web_sys::HtmlVideoElement>(&ev).current_time())
+                }
+                on:durationchange=move |ev| {
+                    let duration = event_target::<web_sys::HtmlVideoElement>(&ev).duration();
+                    set_duration.set(if duration.is_finite() {duration} else {0.0});
+                }
+            ></video>
             <canvas class="canvas-app" node_ref=canvas_node_ref></canvas>
         </div>
+        <div class="source-bar">
+            <SourcePicker on_select_source=on_select_source on_select_image=on_select_image/>
+            <Show when=move || !is_still_image_mode.get() && !is_source_ended.get() fallback=|_| ()>
+                <button on:click=toggle_tracker_pause.clone()>
+                    {move || if is_tracker_paused.get() {"再開"} else {"一時停止"}}
+                </button>
+            </Show>
+            {
+                move || {
+                    (is_seekable.get() && !is_still_image_mode.get()).then(|| view!{cx,
+                        <PlaybackControls
+                            video_node_ref=video_node_ref
+                            is_paused=is_paused
+                            current_time=current_time
+                            duration=duration
+                        />
+                    })
+                }
+            }
+            <Show when=move || is_still_image_mode.get() fallback=|_| ()>
+                <StillImagePanel
+                    selected=still_image_selected
+                    export_mirrored=still_image_export_mirrored
+                    on_download=on_download_still_image.clone()
+                />
+            </Show>
+        </div>
         {
             move || {
                 show_info.get().map(|show_info_text|{
@@ -0,0 +1,178 @@
+use super::geometry::{Rect, Transform};
+use super::video_face_tracker::{RawDetection, RoiSnapshot};
+use crate::error::AppError;
+
+use wasm_bindgen::JsValue;
+
+/// IDごとの色相をずらす角度[°](黄金角)．続くIDが似た色にならないようにする
+const TRACK_HUE_STEP: f64 = 137.508;
+
+/// 検出器の矩形の色
+const RAW_DETECTION_COLOR: &str = "#FFFFFF";
+
+/// 枠を表示する場合(デバッグ表示)に描画する層
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DebugViewOptions {
+    /// 検出器が出力した矩形とスコア(破線)
+    pub raw_detections: bool,
+    /// トラッカーが追跡している矩形(実線)
+    pub tracked_boxes: bool,
+    /// トラックのID・状態・回転角
+    pub labels: bool,
+    /// 特徴点
+    pub landmarks: bool,
+    /// 位置の履歴の軌跡(古いものほど薄く描く)
+    pub trails: bool,
+}
+
+impl Default for DebugViewOptions {
+    fn default() -> Self {
+        Self {
+            raw_detections: true,
+            tracked_boxes: true,
+            labels: true,
+            landmarks: true,
+            trails: true,
+        }
+    }
+}
+
+/// トラックIDごとの色．同じIDは常に同じ色になる
+pub fn track_color(id: u32) -> String {
+    format!("hsl({:.0}, 90%, 55%)", (id as f64 * TRACK_HUE_STEP) % 360.0)
+}
+
+/// 検出器とトラッカーの結果を描画する
+/// - input_to_canvas: 入力画像の座標から表示の座標への変換
+pub fn draw_debug_view(
+    context: &web_sys::CanvasRenderingContext2d,
+    input_to_canvas: &Transform,
+    detections: &[RawDetection],
+    rois: &[&RoiSnapshot],
+    trails: &[(u32, Vec<Rect>)],
+    options: &DebugViewOptions,
+) -> Result<(), AppError> {
+    context.save();
+    let draw_res = draw_layers(context, input_to_canvas, detections, rois, trails, options);
+    context.restore();
+    draw_res
+}
+
+fn draw_layers(
+    context: &web_sys::CanvasRenderingContext2d,
+    input_to_canvas: &Transform,
+    detections: &[RawDetection],
+    rois: &[&RoiSnapshot],
+    trails: &[(u32, Vec<Rect>)],
+    options: &DebugViewOptions,
+) -> Result<(), AppError> {
+    let stroke = |rect: &Rect| context.stroke_rect(rect.x, rect.y, rect.width, rect.height);
+    let set_line_dash = |segments: &[f64]| {
+        let segments = segments
+            .iter()
+            .map(|&segment| JsValue::from_f64(segment))
+            .collect::<js_sys::Array>();
+        context.set_line_dash(&segments)
+    };
+
+    if options.raw_detections {
+        let color = JsValue::from_str(RAW_DETECTION_COLOR);
+        context.set_stroke_style(&color);
+        context.set_fill_style(&color);
+        set_line_dash(&[6.0, 4.0])?;
+        for detection in detections {
+            let rect = input_to_canvas.apply_rect(&Rect::from(&detection.bbox));
+            stroke(&rect);
+            // スコアは矩形の下に表示してトラックのラベルと重ならないようにする
+            context.fill_text(
+                &format!("score: {:.1}", detection.score),
+                rect.x,
+                rect.bottom() + 20.0,
+            )?;
+        }
+    }
+
+    set_line_dash(&[])?;
+    if options.trails {
+        for (id, trail) in trails {
+            context.set_stroke_style(&JsValue::from_str(&track_color(*id)));
+            draw_trail(context, input_to_canvas, trail);
+        }
+    }
+
+    for roi in rois {
+        let color = JsValue::from_str(&track_color(roi.id));
+        context.set_stroke_style(&color);
+        context.set_fill_style(&color);
+        let rect = input_to_canvas.apply_rect(&roi.rect);
+
+        if options.tracked_boxes {
+            stroke(&rect);
+        }
+
+        if options.labels {
+            let roi_text = match roi.roll_angle {
+                Some(roll_angle) => format!(
+                    "id: {} {:?}({}) roll: {:.0}°",
+                    roi.id,
+                    roi.state,
+                    roi.missed_updates,
+                    input_to_canvas.apply_angle(roll_angle).to_degrees()
+                ),
+                None => format!("id: {} {:?}({})", roi.id, roi.state, roi.missed_updates),
+            };
+            context.fill_text(&roi_text, rect.x, rect.y)?;
+        }
+
+        if options.landmarks {
+            if let Some(landmarks) = roi.landmarks.as_ref() {
+                for point in [
+                    &landmarks.left_eye,
+                    &landmarks.right_eye,
+                    &landmarks.nose,
+                    &landmarks.left_mouth,
+                    &landmarks.right_mouth,
+                ] {
+                    let (x, y) = input_to_canvas.apply_point(point.x, point.y);
+                    context.fill_rect(x - 2.0, y - 2.0, 4.0, 4.0);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 位置の履歴の中心を結ぶ折れ線を描く．区間ごとに新しいものほど不透明にする
+fn draw_trail(
+    context: &web_sys::CanvasRenderingContext2d,
+    input_to_canvas: &Transform,
+    trail: &[Rect],
+) {
+    let points = trail
+        .iter()
+        .map(|rect| input_to_canvas.apply_point(rect.center_x(), rect.center_y()))
+        .collect::<Vec<_>>();
+    let segment_count = points.len().saturating_sub(1);
+    for (i, segment) in points.windows(2).enumerate() {
+        let ((x0, y0), (x1, y1)) = (segment[0], segment[1]);
+        context.set_global_alpha((i + 1) as f64 / segment_count as f64);
+        context.begin_path();
+        context.move_to(x0, y0);
+        context.line_to(x1, y1);
+        context.stroke();
+    }
+    context.set_global_alpha(1.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn track_colors_are_stable_and_distinct() {
+        assert_eq!(track_color(3), track_color(3));
+        for id in 0..16 {
+            assert_ne!(track_color(id), track_color(id + 1));
+        }
+    }
+}
@@ -1,6 +1,10 @@
 use crate::error::AppError;
 
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
 
 /// documentを取得するヘルパー関数
 pub fn document() -> web_sys::Document {
@@ -12,6 +16,168 @@ pub fn window() -> web_sys::Window {
     leptos::window()
 }
 
+/// performance.now()[ms]を取得するヘルパー関数．取得できない場合は0
+pub fn performance_now() -> f64 {
+    window()
+        .performance()
+        .map(|performance| performance.now())
+        .unwrap_or(0.0)
+}
+
+/// 表示しないキャンバスを作成するヘルパー関数
+pub fn create_hidden_canvas(
+    width: u32,
+    height: u32,
+) -> Result<web_sys::HtmlCanvasElement, AppError> {
+    let canvas = document()
+        .create_element("canvas")?
+        .dyn_into::<web_sys::HtmlCanvasElement>()
+        .map_err(|element| {
+            AppError::DomError(format!(
+                "Cannot convert from element: {element:?}, into HtmlCanvasElement"
+            ))
+        })?;
+    canvas.set_width(width);
+    canvas.set_height(height);
+    canvas.style().set_css_text("display:none");
+    Ok(canvas)
+}
+
+/// 画像を読み込んでデコードする
+pub async fn load_image(src: &str) -> Result<web_sys::HtmlImageElement, AppError> {
+    let image = document()
+        .create_element("img")?
+        .dyn_into::<web_sys::HtmlImageElement>()
+        .map_err(|element| {
+            AppError::DomError(format!(
+                "Cannot convert from: {element:?} into {}",
+                std::any::type_name::<web_sys::HtmlImageElement>()
+            ))
+        })?;
+    image.set_src(src);
+    JsFuture::from(image.decode()).await?;
+
+    if image.natural_width() == 0 || image.natural_height() == 0 {
+        return Err(AppError::OtherError(format!("Image has no size: {src}")));
+    }
+    Ok(image)
+}
+
+/// イベントリスナーのRAII．ドロップ時にリスナーを削除する
+pub struct EventListener {
+    target: web_sys::EventTarget,
+    event_name: String,
+    closure: Closure<dyn FnMut(web_sys::Event)>,
+}
+
+impl EventListener {
+    pub fn new<F>(
+        target: &web_sys::EventTarget,
+        event_name: &str,
+        callback: F,
+    ) -> Result<Self, AppError>
+    where
+        F: 'static + FnMut(web_sys::Event),
+    {
+        let closure = Closure::wrap(Box::new(callback) as Box<dyn FnMut(web_sys::Event)>);
+        target.add_event_listener_with_callback(event_name, closure.as_ref().unchecked_ref())?;
+        Ok(Self {
+            target: target.clone(),
+            event_name: event_name.to_string(),
+            closure,
+        })
+    }
+}
+
+impl Drop for EventListener {
+    fn drop(&mut self) {
+        if let Err(e) = self.target.remove_event_listener_with_callback(
+            &self.event_name,
+            self.closure.as_ref().unchecked_ref(),
+        ) {
+            log::error!("{e:?}");
+        }
+    }
+}
+
+/// 次のフレームを予約し直すクロージャの置き場所
+type FrameClosureCell = Rc<RefCell<Option<Closure<dyn FnMut(f64)>>>>;
+
+/// requestAnimationFrameで毎フレームコールバックを呼ぶループのRAII．ドロップ時に止める
+pub struct AnimationFrameLoop {
+    /// 予約中のフレームのハンドル
+    handle: Rc<Cell<Option<i32>>>,
+    /// 次のフレームを予約し直すクロージャ．クロージャ自身は弱参照で持つ
+    _closure: FrameClosureCell,
+}
+
+impl AnimationFrameLoop {
+    /// callbackにはフレームの時刻[ms](performance.now()と同じ基準)を渡す
+    pub fn new<F>(mut callback: F) -> Result<Self, AppError>
+    where
+        F: 'static + FnMut(f64),
+    {
+        let closure_cell: FrameClosureCell = Rc::new(RefCell::new(None));
+        let handle = Rc::new(Cell::new(None));
+
+        let closure = Closure::wrap(Box::new({
+            let weak_closure = Rc::downgrade(&closure_cell);
+            let handle = handle.clone();
+            move |time: f64| {
+                callback(time);
+                let Some(closure_cell) = weak_closure.upgrade() else {
+                    return;
+                };
+                let request_res = closure_cell.borrow().as_ref().map(|closure| {
+                    window().request_animation_frame(closure.as_ref().unchecked_ref())
+                });
+                match request_res {
+                    Some(Ok(id)) => handle.set(Some(id)),
+                    Some(Err(e)) => log::error!("{e:?}"),
+                    None => {}
+                }
+            }
+        }) as Box<dyn FnMut(f64)>);
+
+        handle.set(Some(
+            window().request_animation_frame(closure.as_ref().unchecked_ref())?,
+        ));
+        *closure_cell.borrow_mut() = Some(closure);
+
+        Ok(Self {
+            handle,
+            _closure: closure_cell,
+        })
+    }
+}
+
+impl Drop for AnimationFrameLoop {
+    fn drop(&mut self) {
+        if let Some(id) = self.handle.take() {
+            if let Err(e) = window().cancel_animation_frame(id) {
+                log::error!("{e:?}");
+            }
+        }
+    }
+}
+
+/// URLの内容をファイルとしてダウンロードさせるヘルパー関数
+pub fn download_url(href: &str, file_name: &str) -> Result<(), AppError> {
+    let anchor = document()
+        .create_element("a")?
+        .dyn_into::<web_sys::HtmlAnchorElement>()
+        .map_err(|element| {
+            AppError::DomError(format!(
+                "Cannot convert from: {element:?} into {}",
+                std::any::type_name::<web_sys::HtmlAnchorElement>()
+            ))
+        })?;
+    anchor.set_href(href);
+    anchor.set_download(file_name);
+    anchor.click();
+    Ok(())
+}
+
 /// キャンバスのコンテキストからキャンバスを取得するヘルパー関数
 pub fn canvas(
     context2d: &web_sys::CanvasRenderingContext2d,
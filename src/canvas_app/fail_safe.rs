@@ -0,0 +1,286 @@
+use super::geometry::{Rect, Size, Transform};
+use super::redaction::{draw_video_in_rects, redact, RedactionOptions, RedactionStyle};
+use crate::error::AppError;
+
+use wasm_bindgen::JsValue;
+
+/// 映像全体をぼかす場合の半径(表示の幅に対する比率)
+const FAIL_SAFE_BLUR_RADIUS_RATIO: f64 = 0.05;
+
+/// 追跡が不安定な時に映像全体を隠す方法
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum FailSafeMode {
+    /// 隠さない
+    #[default]
+    Off,
+    /// 黒く塗りつぶす
+    Blank,
+    /// 映像全体をぼかす
+    Blur,
+}
+
+impl FailSafeMode {
+    /// セレクトボックスの値に変換
+    pub fn to_value(self) -> &'static str {
+        match self {
+            FailSafeMode::Off => "off",
+            FailSafeMode::Blank => "blank",
+            FailSafeMode::Blur => "blur",
+        }
+    }
+
+    /// セレクトボックスの値から変換
+    pub fn from_value(value: &str) -> Self {
+        match value {
+            "blank" => FailSafeMode::Blank,
+            "blur" => FailSafeMode::Blur,
+            _ => FailSafeMode::Off,
+        }
+    }
+}
+
+/// 映像全体を隠している理由
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FailSafeReason {
+    /// トラッカーの初期化直後で，まだ安定した検出が無い
+    Starting,
+    /// トラッカーの更新に失敗した
+    StepError,
+    /// 検出に時間がかかりすぎた
+    OverBudget,
+    /// 中央付近のトラックを見失った
+    LostNearCenter,
+}
+
+impl FailSafeReason {
+    /// 隠している間に表示する文章
+    pub fn message(self) -> &'static str {
+        match self {
+            FailSafeReason::Starting => "検出の開始を待っています",
+            FailSafeReason::StepError => "検出に失敗したため映像を隠しています",
+            FailSafeReason::OverBudget => "検出が遅れているため映像を隠しています",
+            FailSafeReason::LostNearCenter => "中央の顔を見失ったため映像を隠しています",
+        }
+    }
+}
+
+/// 追跡が不安定な時に映像全体を隠す機能のオプション
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FailSafeOptions {
+    pub mode: FailSafeMode,
+    /// 1回の検出にかけてよい時間[ms]．超えると隠す
+    pub span_time_budget: f64,
+    /// 中央とみなす領域の大きさ(入力画像の幅・高さに対する比率)．この領域でトラックを見失うと隠す
+    pub center_region_ratio: f64,
+    /// 再び表示するまでに必要な，連続した安定した検出の回数
+    pub recovery_count: u32,
+}
+
+impl Default for FailSafeOptions {
+    fn default() -> Self {
+        Self {
+            mode: Default::default(),
+            span_time_budget: 150.0,
+            center_region_ratio: 0.5,
+            recovery_count: 5,
+        }
+    }
+}
+
+/// 映像全体を隠すかどうかの状態．一度隠すと，全てのトラックが検出された更新が続くまで隠し続ける(フェイルクローズ)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FailSafe {
+    /// 隠している理由．Noneの場合は表示している
+    reason: Option<FailSafeReason>,
+    /// 隠してから連続した安定した検出の回数
+    stable_count: u32,
+}
+
+impl Default for FailSafe {
+    /// トラッカーの初期化直後は安定した検出があるまで隠す
+    fn default() -> Self {
+        Self {
+            reason: Some(FailSafeReason::Starting),
+            stable_count: 0,
+        }
+    }
+}
+
+impl FailSafe {
+    /// トラッカーの更新に失敗した
+    pub fn step_failed(&mut self) {
+        self.trip(FailSafeReason::StepError);
+    }
+
+    /// トラッカーの更新の結果を反映する
+    /// - image_size: 入力画像のサイズ
+    /// - lost_rects: 削除されたトラックの矩形(入力画像の座標)
+    /// - is_all_detected: 全てのトラックが今回の更新で検出されたかどうか
+    pub fn step_succeeded(
+        &mut self,
+        options: &FailSafeOptions,
+        span_time: f64,
+        image_size: Size,
+        lost_rects: &[Rect],
+        is_all_detected: bool,
+    ) {
+        let ratio = options.center_region_ratio.clamp(0.0, 1.0);
+        let center_region = Rect::new(
+            image_size.width * (1.0 - ratio) / 2.0,
+            image_size.height * (1.0 - ratio) / 2.0,
+            image_size.width * ratio,
+            image_size.height * ratio,
+        );
+
+        if span_time > options.span_time_budget {
+            self.trip(FailSafeReason::OverBudget);
+        } else if lost_rects
+            .iter()
+            .any(|rect| center_region.contains(rect.center_x(), rect.center_y()))
+        {
+            self.trip(FailSafeReason::LostNearCenter);
+        } else if !is_all_detected {
+            self.stable_count = 0;
+        } else if self.reason.is_some() {
+            self.stable_count += 1;
+            if self.stable_count >= options.recovery_count {
+                self.reason = None;
+                self.stable_count = 0;
+            }
+        }
+    }
+
+    fn trip(&mut self, reason: FailSafeReason) {
+        self.reason = Some(reason);
+        self.stable_count = 0;
+    }
+
+    /// 映像全体を隠す理由．隠さない場合(オフの場合も)はNone
+    pub fn hiding_reason(&self, options: &FailSafeOptions) -> Option<FailSafeReason> {
+        if options.mode == FailSafeMode::Off {
+            None
+        } else {
+            self.reason
+        }
+    }
+}
+
+/// 表示全体(canvas_rect)を隠し，理由を表示する．ぼかす場合は元の映像を描画してからぼかす
+/// - video_to_canvas: 映像の座標から表示の座標への変換
+pub fn cover_frame(
+    context: &web_sys::CanvasRenderingContext2d,
+    video: &web_sys::HtmlVideoElement,
+    video_to_canvas: &Transform,
+    canvas_rect: &Rect,
+    mode: FailSafeMode,
+    reason: FailSafeReason,
+) -> Result<(), AppError> {
+    if mode == FailSafeMode::Off {
+        return Ok(());
+    }
+
+    // ぼかす場合も余白の透明な画素が混ざらないように先に塗りつぶす
+    context.save();
+    context.set_fill_style(&JsValue::from_str("black"));
+    context.fill_rect(
+        canvas_rect.x,
+        canvas_rect.y,
+        canvas_rect.width,
+        canvas_rect.height,
+    );
+    context.restore();
+
+    if mode == FailSafeMode::Blur && video.video_width() > 0 {
+        draw_video_in_rects(context, video, video_to_canvas, &[*canvas_rect])?;
+        redact(
+            context,
+            canvas_rect,
+            None,
+            &RedactionOptions {
+                style: RedactionStyle::Blur,
+                blur_radius_ratio: FAIL_SAFE_BLUR_RADIUS_RATIO,
+                ..Default::default()
+            },
+        )?;
+    }
+
+    context.fill_text(reason.message(), 20.0, canvas_rect.height / 2.0)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IMAGE_SIZE: Size = Size {
+        width: 200.0,
+        height: 100.0,
+    };
+
+    fn options() -> FailSafeOptions {
+        FailSafeOptions {
+            mode: FailSafeMode::Blank,
+            recovery_count: 3,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn hidden_until_stable_detections() {
+        let options = options();
+        let mut fail_safe = FailSafe::default();
+        assert_eq!(
+            fail_safe.hiding_reason(&options),
+            Some(FailSafeReason::Starting)
+        );
+
+        fail_safe.step_succeeded(&options, 10.0, IMAGE_SIZE, &[], true);
+        fail_safe.step_succeeded(&options, 10.0, IMAGE_SIZE, &[], true);
+        // 検出されないトラックがあると数え直す
+        fail_safe.step_succeeded(&options, 10.0, IMAGE_SIZE, &[], false);
+        fail_safe.step_succeeded(&options, 10.0, IMAGE_SIZE, &[], true);
+        fail_safe.step_succeeded(&options, 10.0, IMAGE_SIZE, &[], true);
+        assert!(fail_safe.hiding_reason(&options).is_some());
+        fail_safe.step_succeeded(&options, 10.0, IMAGE_SIZE, &[], true);
+        assert_eq!(fail_safe.hiding_reason(&options), None);
+
+        fail_safe.step_failed();
+        assert_eq!(
+            fail_safe.hiding_reason(&options),
+            Some(FailSafeReason::StepError)
+        );
+        assert_eq!(fail_safe.hiding_reason(&FailSafeOptions::default()), None);
+    }
+
+    #[test]
+    fn trips_on_budget_and_lost_center_track() {
+        let options = options();
+        let stable = || {
+            let mut fail_safe = FailSafe::default();
+            for _ in 0..3 {
+                fail_safe.step_succeeded(&options, 10.0, IMAGE_SIZE, &[], true);
+            }
+            fail_safe
+        };
+
+        let mut fail_safe = stable();
+        fail_safe.step_succeeded(&options, 500.0, IMAGE_SIZE, &[], true);
+        assert_eq!(
+            fail_safe.hiding_reason(&options),
+            Some(FailSafeReason::OverBudget)
+        );
+
+        // 端で見失った場合は隠さない
+        let mut fail_safe = stable();
+        let edge = Rect::new(0.0, 0.0, 20.0, 20.0);
+        fail_safe.step_succeeded(&options, 10.0, IMAGE_SIZE, &[edge], true);
+        assert_eq!(fail_safe.hiding_reason(&options), None);
+
+        let center = Rect::new(90.0, 40.0, 20.0, 20.0);
+        fail_safe.step_succeeded(&options, 10.0, IMAGE_SIZE, &[center], true);
+        assert_eq!(
+            fail_safe.hiding_reason(&options),
+            Some(FailSafeReason::LostNearCenter)
+        );
+    }
+}
@@ -0,0 +1,645 @@
+use super::debug_view::{draw_debug_view, DebugViewOptions};
+use super::dom_utils::performance_now;
+use super::fail_safe::{cover_frame, FailSafe, FailSafeOptions, FailSafeReason};
+use super::geometry::{Rect, Transform};
+use super::laughing_man_image::{
+    draw_overlays, LaughingManCanvasRenderer, LaughingManImage, LaughingManOptions,
+    LaughingManState, OverlayRenderer,
+};
+use super::overlay_assignment::OverlayAssigner;
+use super::overlay_registry::OverlayRegistry;
+use super::performance_hud::{draw_performance_hud, dropped_frames, PerformanceStats};
+use super::redaction::{draw_video_in_rects, redact, RedactionOptions, RedactionStyle};
+use super::roi_motion::RoiMotion;
+use super::still_image::StillImage;
+use super::video_face_tracker::{
+    check_tracking, RawDetection, RoiGrowthOptions, RoiSnapshot, TrackerRoi, TrackingCheck,
+    VideoFaceInfo, VideoFaceTracker,
+};
+use super::CanvasLayout;
+
+use leptos::*;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+/// トラッカーを1回更新した結果
+pub struct TrackedFrame {
+    pub rois: Vec<RoiSnapshot>,
+    /// 各トラックの位置の履歴(トラックID, 検出された矩形)．枠の表示で軌跡を描く場合のみ
+    pub trails: Vec<(u32, Vec<Rect>)>,
+    /// 検出器が出力した顔
+    pub detections: Vec<RawDetection>,
+    /// 検出にかかった時間[ms]
+    pub span_time: f64,
+    /// 入力のビデオ(ぼかしとモザイクで元の映像を描画する)
+    pub stream_video: web_sys::HtmlVideoElement,
+    /// ビデオの座標から入力画像の座標への変換
+    pub video_to_input: Transform,
+    /// 映像全体を隠す理由．Noneの場合は隠さない
+    pub fail_safe_reason: Option<FailSafeReason>,
+    /// 合成映像の場合は真値の矩形とトラッキング結果の比較
+    pub ground_truth: Option<(Vec<Rect>, TrackingCheck)>,
+}
+
+/// 描画するroiと覆う矩形(入力画像の座標)の組．
+/// roi_motionを指定した場合は時刻time[ms]まで外挿した位置に動かし，roiも同じだけ平行移動する
+fn display_rois(
+    rois: &[RoiSnapshot],
+    roi_motion: Option<&RoiMotion>,
+    time: f64,
+) -> Vec<(RoiSnapshot, Rect)> {
+    rois.iter()
+        .map(
+            |roi| match roi_motion.and_then(|roi_motion| roi_motion.sample(roi.id, time)) {
+                Some(rect) => (
+                    roi.translated(
+                        rect.center_x() - roi.cover_rect.center_x(),
+                        rect.center_y() - roi.cover_rect.center_y(),
+                    ),
+                    rect,
+                ),
+                None => (roi.clone(), roi.cover_rect),
+            },
+        )
+        .collect()
+}
+
+/// インターバルと描画ループで行う，トラッカーの更新とキャンバスへの描画．
+/// 滑らかに動かす場合，インターバルではトラッカーの更新だけを行い，描画ループで外挿した位置に描画する．
+/// 各状態はコンポーネントと共有する
+#[derive(Clone)]
+pub struct FrameLoop {
+    pub canvas_context: web_sys::CanvasRenderingContext2d,
+    /// 笑い男画像(DOM)を追加する要素
+    pub container_node: web_sys::Node,
+    pub video_face_tracker: Rc<RefCell<Option<VideoFaceTracker>>>,
+    pub still_image: Rc<RefCell<Option<StillImage>>>,
+    pub canvas_layout: Rc<Cell<CanvasLayout>>,
+    pub laughing_man_images: Rc<RefCell<OverlayRegistry<LaughingManImage>>>,
+    pub laughing_man_renderers: Rc<RefCell<Vec<LaughingManCanvasRenderer>>>,
+    pub overlay_assigner: Rc<RefCell<OverlayAssigner>>,
+    pub roi_motion: Rc<RefCell<RoiMotion>>,
+    pub fail_safe: Rc<Cell<FailSafe>>,
+    pub performance_stats: Rc<RefCell<PerformanceStats>>,
+    pub laughing_man_options: Rc<RefCell<LaughingManOptions>>,
+    pub redaction_options: Rc<Cell<RedactionOptions>>,
+    pub fail_safe_options: Rc<Cell<FailSafeOptions>>,
+    pub debug_view_options: Rc<Cell<DebugViewOptions>>,
+    pub show_performance_hud: Rc<Cell<bool>>,
+    pub interval_span: Rc<Cell<u32>>,
+    pub is_source_ended: ReadSignal<bool>,
+    pub set_is_source_ended: WriteSignal<bool>,
+    pub set_show_info: WriteSignal<Option<String>>,
+    /// 最後にトラッカーを更新した結果(描画ループで描き直す)
+    pub last_frame: Rc<RefCell<Option<TrackedFrame>>>,
+}
+
+impl FrameLoop {
+    /// インターバル1回分の処理．処理時間を統計に記録する
+    pub fn tick(&self) {
+        let tick_start_time = performance_now();
+        if self.step() {
+            self.performance_stats.borrow_mut().record_tick(
+                performance_now() - tick_start_time,
+                self.interval_span.get(),
+            );
+        }
+    }
+
+    /// 描画ループの1回分．画面の更新ごとに笑い男画像とキャンバスの描画の位置を外挿する
+    /// - time: フレームの時刻[ms]
+    pub fn animate(&self, time: f64) {
+        if !self.laughing_man_options.borrow().smooth_motion {
+            return;
+        }
+        // 静止画モードの場合はキャンバスを更新しない
+        if self.still_image.borrow().is_some() {
+            return;
+        }
+        // 一時停止中やソースの終了後は最後のフレームの表示を維持する
+        let is_running =
+            self.video_face_tracker
+                .borrow()
+                .as_ref()
+                .is_some_and(|video_face_tracker| {
+                    !video_face_tracker.is_paused() && !video_face_tracker.is_source_ended()
+                });
+        if !is_running {
+            return;
+        }
+
+        let layout = self.canvas_layout.get();
+        let roi_motion = self.roi_motion.borrow();
+        for (id, laughing_man_img) in self.laughing_man_images.borrow().iter() {
+            if let Some(rect) = roi_motion.sample(id, time) {
+                if let Err(e) = laughing_man_img.step(layout.laughing_man_state(id, &rect)) {
+                    log::error!("{e}");
+                }
+            }
+        }
+
+        if let Some(frame) = self.last_frame.borrow().as_ref() {
+            self.render_frame(frame, time);
+        }
+    }
+
+    /// トラッカーを更新して描画する．
+    /// 更新しなかった場合(静止画モード，一時停止中，ソースの終了)はfalse
+    fn step(&self) -> bool {
+        // 静止画モードの場合はキャンバスを更新しない
+        if self.still_image.borrow().is_some() {
+            return false;
+        }
+        let mut video_face_tracker = self.video_face_tracker.borrow_mut();
+        let Some(video_face_tracker) = video_face_tracker.as_mut() else {
+            return false;
+        };
+        // 一時停止中は最後のフレームの表示を維持する
+        if video_face_tracker.is_paused() {
+            return false;
+        }
+        // ソースが終了した場合は入力ソースの選択に戻る
+        if video_face_tracker.is_source_ended() {
+            self.end_source();
+            return false;
+        }
+
+        match self.update_tracker(video_face_tracker) {
+            Some(frame) => {
+                // 滑らかに動かす場合は描画ループで描画する
+                if !self.laughing_man_options.borrow().smooth_motion {
+                    self.render_frame(&frame, performance_now());
+                }
+                *self.last_frame.borrow_mut() = Some(frame);
+            }
+            None => {
+                // 描画ループでも最後のフレームごと隠す
+                let fail_safe_reason = self
+                    .fail_safe
+                    .get()
+                    .hiding_reason(&self.fail_safe_options.get());
+                if let Some(frame) = self.last_frame.borrow_mut().as_mut() {
+                    frame.fail_safe_reason = fail_safe_reason;
+                }
+                self.cover_failed_frame(video_face_tracker);
+            }
+        }
+        true
+    }
+
+    /// 表示を消し，入力ソースの選択を促す
+    fn end_source(&self) {
+        if self.is_source_ended.get_untracked() {
+            return;
+        }
+        let layout = self.canvas_layout.get();
+        self.canvas_context
+            .clear_rect(0.0, 0.0, layout.width, layout.height);
+        self.laughing_man_images.borrow_mut().clear();
+        *self.last_frame.borrow_mut() = None;
+        self.set_is_source_ended.set(true);
+        self.set_show_info.set(Some(
+            "入力が終了しました。入力ソースを選択して下さい".to_string(),
+        ));
+    }
+
+    /// トラッカーを更新し，追跡の安定性と笑い男画像(DOM)に反映する．更新に失敗した場合はNone
+    fn update_tracker(&self, video_face_tracker: &mut VideoFaceTracker) -> Option<TrackedFrame> {
+        // 合成映像の場合は真値の矩形
+        let ground_truth = video_face_tracker.ground_truth();
+        let stream_video = video_face_tracker.stream_video().clone();
+        let (image_width, image_height) = video_face_tracker.image_size();
+        let video_to_input = Transform::scale(
+            image_width as f64 / stream_video.video_width() as f64,
+            image_height as f64 / stream_video.video_height() as f64,
+        );
+        // 見失っている間は覆う範囲を広げる
+        let roi_growth = video_face_tracker.roi_growth();
+
+        let VideoFaceInfo {
+            rois,
+            estimates,
+            detections,
+            added_rois,
+            removed_rois,
+            span_time,
+        } = match video_face_tracker.step() {
+            Ok(video_face_info) => video_face_info,
+            Err(e) => {
+                log::error!("{e}");
+                let mut fail_safe_state = self.fail_safe.get();
+                fail_safe_state.step_failed();
+                self.fail_safe.set(fail_safe_state);
+                return None;
+            }
+        };
+
+        // トラッカーを更新した時刻[ms]
+        let update_time = performance_now();
+        self.performance_stats
+            .borrow_mut()
+            .record_step(update_time, span_time);
+
+        // 描画ループで外挿するためにトラッカーの更新を記録
+        let added_ids = added_rois.iter().map(|roi| roi.id).collect::<Vec<_>>();
+        {
+            let rects = rois
+                .iter()
+                .map(|roi| (roi.id, roi.cover_rect(&roi_growth)))
+                .collect::<Vec<_>>();
+            self.roi_motion
+                .borrow_mut()
+                .update(update_time, &rects, &added_ids);
+        }
+
+        // 追跡が不安定かどうかを判定
+        let fail_safe_options = self.fail_safe_options.get();
+        let fail_safe_reason = {
+            let lost_rects = removed_rois
+                .iter()
+                .map(|roi| roi.rect())
+                .collect::<Vec<_>>();
+            let mut fail_safe_state = self.fail_safe.get();
+            fail_safe_state.step_succeeded(
+                &fail_safe_options,
+                span_time,
+                (image_width, image_height).into(),
+                &lost_rects,
+                rois.iter().all(|roi| roi.is_detected()),
+            );
+            self.fail_safe.set(fail_safe_state);
+            fail_safe_state.hiding_reason(&fail_safe_options)
+        };
+
+        let ground_truth = ground_truth.map(|ground_truth| {
+            let tracking_check = check_tracking(&ground_truth, &rois);
+            (ground_truth, tracking_check)
+        });
+
+        self.update_overlays(&rois, &removed_rois, &roi_growth, update_time);
+
+        // 位置の履歴は軌跡を描く場合だけ写す
+        let trails = if self.redaction_options.get().style == RedactionStyle::Outline
+            && self.debug_view_options.get().trails
+        {
+            rois.iter()
+                .map(|roi| (roi.id, roi.history.iter().map(|point| point.rect).collect()))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        Some(TrackedFrame {
+            rois: rois
+                .iter()
+                .zip(estimates)
+                .map(|(roi, estimate)| roi.snapshot(&roi_growth, estimate))
+                .collect(),
+            trails,
+            detections,
+            span_time,
+            stream_video,
+            video_to_input,
+            fail_safe_reason,
+            ground_truth,
+        })
+    }
+
+    /// 笑い男画像をDOMで表示するかどうか
+    fn is_dom_overlay(&self) -> bool {
+        self.redaction_options.get().style == RedactionStyle::LaughingMan
+            && self.laughing_man_options.borrow().renderer == OverlayRenderer::Dom
+            && !self.laughing_man_renderers.borrow().is_empty()
+    }
+
+    /// 笑い男画像(DOM)の追加・削除と移動．トラックIDで突き合わせ，削除は消えるアニメーションを待つ．
+    /// 画像を作成できなかったトラックは次の更新で作り直す(それまではrender_frameで塗りつぶす)
+    fn update_overlays(
+        &self,
+        rois: &[&TrackerRoi],
+        removed_rois: &[TrackerRoi],
+        roi_growth: &RoiGrowthOptions,
+        update_time: f64,
+    ) {
+        let is_dom_overlay = self.is_dom_overlay();
+        let layout = self.canvas_layout.get();
+        let mut laughing_man_images = self.laughing_man_images.borrow_mut();
+
+        // 削除されたトラックの画像の割り当てを解除．
+        // domの場合は付け替えられるように消えるアニメーションが終わってから解除する
+        let mut failed_ids = Vec::new();
+        if is_dom_overlay {
+            let renderers = self.laughing_man_renderers.borrow();
+            let laughing_man_options = self.laughing_man_options.borrow();
+            let removed_ids = removed_rois.iter().map(|roi| roi.id).collect::<Vec<_>>();
+            let tracks = rois
+                .iter()
+                .map(|roi| {
+                    let state = layout.laughing_man_state(roi.id, &roi.cover_rect(roi_growth));
+                    (state.id, state.rect)
+                })
+                .collect::<Vec<_>>();
+            let reconciliation =
+                laughing_man_images.reconcile(&tracks, &removed_ids, update_time, |id, rect| {
+                    let index = self.overlay_assigner.borrow_mut().assign(
+                        id,
+                        renderers.len(),
+                        laughing_man_options.assignment,
+                    );
+                    LaughingManImage::new(
+                        self.container_node.clone(),
+                        &renderers[index],
+                        laughing_man_options.clone(),
+                        LaughingManState { id, rect: *rect },
+                    )
+                });
+            let mut overlay_assigner = self.overlay_assigner.borrow_mut();
+            for (leaving_id, id) in reconciliation.rematched {
+                overlay_assigner.transfer(leaving_id, id);
+            }
+            for (id, e) in reconciliation.failed {
+                log::error!("{e}");
+                failed_ids.push(id);
+            }
+            for id in laughing_man_images.sweep(update_time) {
+                overlay_assigner.release(id);
+            }
+        } else {
+            let mut overlay_assigner = self.overlay_assigner.borrow_mut();
+            for roi in removed_rois.iter() {
+                overlay_assigner.release(roi.id);
+            }
+            laughing_man_images.clear();
+        }
+
+        // DOMの画像は作成できたトラックとだけ対応する(DOMで表示しない場合は画像を持たない)
+        let overlay_ids = rois
+            .iter()
+            .map(|roi| roi.id)
+            .filter(|id| is_dom_overlay && !failed_ids.contains(id))
+            .collect::<Vec<_>>();
+        debug_assert!(
+            laughing_man_images.is_consistent_with(&overlay_ids),
+            "Overlays are inconsistent with tracks: {overlay_ids:?}"
+        );
+
+        // 笑い男の遷移(描画)
+        for roi in rois {
+            if let Some(laughing_man_img) = laughing_man_images.get(roi.id) {
+                let state = layout.laughing_man_state(roi.id, &roi.cover_rect(roi_growth));
+                if let Err(e) = laughing_man_img.step(state) {
+                    log::error!("{e}");
+                }
+            }
+        }
+    }
+
+    /// トラッカーを更新した結果をキャンバスに描画する
+    /// - time: 描画する時刻[ms]．滑らかに動かす場合はこの時刻まで外挿する．キャンバスの笑い男画像の回転にも使う
+    fn render_frame(&self, frame: &TrackedFrame, time: f64) {
+        let layout = self.canvas_layout.get();
+        let CanvasLayout {
+            width: canvas_app_width,
+            height: canvas_app_height,
+            input_to_canvas,
+        } = layout;
+        let context = &self.canvas_context;
+        let redaction_options = self.redaction_options.get();
+        let laughing_man_options = self.laughing_man_options.borrow();
+        let renderers = self.laughing_man_renderers.borrow();
+        let video_to_canvas = frame.video_to_input.then(&input_to_canvas);
+        let rois = {
+            let roi_motion = self.roi_motion.borrow();
+            display_rois(
+                &frame.rois,
+                laughing_man_options.smooth_motion.then_some(&*roi_motion),
+                time,
+            )
+        };
+
+        // キャンパスの初期化
+        context.clear_rect(0.0, 0.0, canvas_app_width, canvas_app_height);
+
+        let mut canvas_overlay_states = Vec::new();
+        if redaction_options.style == RedactionStyle::LaughingMan {
+            if renderers.is_empty() {
+                // 重ねる画像を読み込めなかった場合も顔を見せないように塗りつぶす
+                let solid_box = redaction_options.opaque_solid_box();
+                for (_, rect) in rois.iter() {
+                    if let Err(e) =
+                        redact(context, &input_to_canvas.apply_rect(rect), None, &solid_box)
+                    {
+                        log::error!("{e}");
+                    }
+                }
+            } else if !self.is_dom_overlay() {
+                // 笑い男モードでキャンバスに描画する場合(DOMの場合はupdate_overlaysで移動する)
+                canvas_overlay_states = rois
+                    .iter()
+                    .map(|(roi, rect)| layout.laughing_man_state(roi.id, rect))
+                    .collect::<Vec<_>>();
+            } else {
+                // DOMの画像を作成できなかったトラックは塗りつぶす
+                let laughing_man_images = self.laughing_man_images.borrow();
+                let solid_box = redaction_options.opaque_solid_box();
+                for (roi, rect) in rois.iter() {
+                    if laughing_man_images.get(roi.id).is_none() {
+                        if let Err(e) =
+                            redact(context, &input_to_canvas.apply_rect(rect), None, &solid_box)
+                        {
+                            log::error!("{e}");
+                        }
+                    }
+                }
+            }
+        } else if redaction_options.style != RedactionStyle::Outline {
+            // 笑い男以外の方法で匿名化する場合
+            let rects = rois
+                .iter()
+                .map(|(_, rect)| input_to_canvas.apply_rect(rect))
+                .collect::<Vec<_>>();
+            // ぼかしとモザイクは元の映像が無い場合(読み込み前や切り替え直後)は透明な画素を加工することになるため塗りつぶす
+            let redaction_options = if redaction_options.style.needs_source_pixels() {
+                if frame.stream_video.video_width() > 0 {
+                    if let Err(e) =
+                        draw_video_in_rects(context, &frame.stream_video, &video_to_canvas, &rects)
+                    {
+                        log::error!("{e}");
+                    }
+                    redaction_options
+                } else {
+                    redaction_options.opaque_solid_box()
+                }
+            } else {
+                redaction_options
+            };
+
+            for ((roi, _), rect) in rois.iter().zip(rects.iter()) {
+                let eyes = roi.landmarks.as_ref().map(|landmarks| {
+                    (
+                        input_to_canvas.apply_point(landmarks.left_eye.x, landmarks.left_eye.y),
+                        input_to_canvas.apply_point(landmarks.right_eye.x, landmarks.right_eye.y),
+                    )
+                });
+                if let Err(e) = redact(context, rect, eyes, &redaction_options) {
+                    log::error!("{e}");
+                }
+            }
+        } else {
+            // 枠を表示する場合
+            if let Err(e) = draw_debug_view(
+                context,
+                &input_to_canvas,
+                &frame.detections,
+                &rois.iter().map(|(roi, _)| roi).collect::<Vec<_>>(),
+                &frame.trails,
+                &self.debug_view_options.get(),
+            ) {
+                log::error!("{e}");
+            }
+        }
+
+        // キャンバスの笑い男画像の描画．z-indexで他の描画との前後を決める
+        let draw_canvas_overlays = || {
+            if let Err(e) = draw_overlays(
+                context,
+                &renderers,
+                &mut self.overlay_assigner.borrow_mut(),
+                &canvas_overlay_states,
+                &laughing_man_options,
+                time / 1000.0,
+            ) {
+                log::error!("{e}");
+            }
+        };
+        let is_overlay_above_canvas = laughing_man_options.is_above_canvas();
+        if !is_overlay_above_canvas {
+            draw_canvas_overlays();
+        }
+
+        // 追跡が不安定な場合は映像全体を隠す
+        if let Some(reason) = frame.fail_safe_reason {
+            self.cover(&frame.stream_video, &video_to_canvas, reason);
+        }
+
+        self.draw_diagnostics(frame, &input_to_canvas);
+
+        if is_overlay_above_canvas {
+            draw_canvas_overlays();
+        }
+
+        // 検出の処理時間などのパネル
+        if self.show_performance_hud.get() {
+            if let Err(e) = draw_performance_hud(
+                context,
+                20.0,
+                50.0,
+                &self.performance_stats.borrow(),
+                self.interval_span.get(),
+                dropped_frames(&frame.stream_video),
+            ) {
+                log::error!("{e}");
+            }
+        }
+    }
+
+    /// トラッカーを更新できなかった場合に，追跡できないものとして映像全体を隠す
+    fn cover_failed_frame(&self, video_face_tracker: &VideoFaceTracker) {
+        let Some(reason) = self
+            .fail_safe
+            .get()
+            .hiding_reason(&self.fail_safe_options.get())
+        else {
+            return;
+        };
+        let layout = self.canvas_layout.get();
+        let stream_video = video_face_tracker.stream_video();
+        let (image_width, image_height) = video_face_tracker.image_size();
+        let video_to_canvas = Transform::scale(
+            image_width as f64 / stream_video.video_width() as f64,
+            image_height as f64 / stream_video.video_height() as f64,
+        )
+        .then(&layout.input_to_canvas);
+        self.canvas_context
+            .clear_rect(0.0, 0.0, layout.width, layout.height);
+        self.cover(stream_video, &video_to_canvas, reason);
+    }
+
+    /// 映像全体を隠す
+    fn cover(
+        &self,
+        stream_video: &web_sys::HtmlVideoElement,
+        video_to_canvas: &Transform,
+        reason: FailSafeReason,
+    ) {
+        let layout = self.canvas_layout.get();
+        if let Err(e) = cover_frame(
+            &self.canvas_context,
+            stream_video,
+            video_to_canvas,
+            &Rect::new(0.0, 0.0, layout.width, layout.height),
+            self.fail_safe_options.get().mode,
+            reason,
+        ) {
+            log::error!("{e}");
+        }
+    }
+
+    /// 検出した顔の数と時間，合成映像の場合は真値とトラッキング結果の比較を描画する
+    fn draw_diagnostics(&self, frame: &TrackedFrame, input_to_canvas: &Transform) {
+        let context = &self.canvas_context;
+        let text_info = format!(
+            "{:03} faces detected in {:.1}[ms]",
+            frame.rois.len(),
+            frame.span_time
+        );
+        context
+            .fill_text(&text_info, 20.0, 20.0)
+            .expect("Cannot add text");
+
+        if let Some((ground_truth, tracking_check)) = frame.ground_truth.as_ref() {
+            context.set_stroke_style(&wasm_bindgen::JsValue::from_str("#00C853"));
+            for face in ground_truth {
+                let rect = input_to_canvas.apply_rect(face);
+                context.stroke_rect(rect.x, rect.y, rect.width, rect.height);
+            }
+            context.set_stroke_style(&wasm_bindgen::JsValue::from_str("#FF0000"));
+
+            let check_info = format!(
+                "ground truth: {}/{} tracked, center error {:.1}[px]",
+                tracking_check.matched_count,
+                tracking_check.ground_truth_count,
+                tracking_check.mean_center_error * input_to_canvas.length_scale()
+            );
+            context
+                .fill_text(&check_info, 20.0, 40.0)
+                .expect("Cannot add text");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_rois_follow_extrapolated_motion() {
+        let roi = TrackerRoi::new(1, 10.0, 0.0, 10.0, 10.0);
+        let rois = [roi.snapshot(&RoiGrowthOptions::default(), roi.estimate())];
+        let mut roi_motion = RoiMotion::default();
+        roi_motion.update(0.0, &[(1, Rect::new(0.0, 0.0, 10.0, 10.0))], &[1]);
+        roi_motion.update(100.0, &[(1, roi.rect())], &[]);
+
+        // 外挿しない場合はトラッカーの位置のまま
+        let (display_roi, rect) = display_rois(&rois, None, 150.0).pop().unwrap();
+        assert_eq!(rect, roi.rect());
+        assert_eq!(display_roi.rect, roi.rect());
+
+        // 更新の間は外挿した位置に矩形とroiを動かす
+        let (display_roi, rect) = display_rois(&rois, Some(&roi_motion), 150.0).pop().unwrap();
+        assert_eq!(rect, Rect::new(15.0, 0.0, 10.0, 10.0));
+        assert_eq!(display_roi.rect, rect);
+        assert_eq!(display_roi.cover_rect, rect);
+        assert_eq!(display_roi.id, 1);
+    }
+}
@@ -0,0 +1,367 @@
+/// 大きさ
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct Size {
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Size {
+    pub fn new(width: f64, height: f64) -> Self {
+        Self { width, height }
+    }
+}
+
+impl From<(u32, u32)> for Size {
+    fn from((width, height): (u32, u32)) -> Self {
+        Self::new(width as f64, height as f64)
+    }
+}
+
+/// 矩形．座標は負の値やはみ出しも許す
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    pub fn new(x: f64, y: f64, width: f64, height: f64) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn right(&self) -> f64 {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> f64 {
+        self.y + self.height
+    }
+
+    pub fn center_x(&self) -> f64 {
+        self.x + self.width / 2.0
+    }
+
+    pub fn center_y(&self) -> f64 {
+        self.y + self.height / 2.0
+    }
+
+    /// 点が矩形の内側にあるかどうか
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        self.x <= x && x < self.right() && self.y <= y && y < self.bottom()
+    }
+
+    /// selfからotherへの線形補間．tが1を超える場合は外挿になる
+    pub fn lerp(&self, other: &Rect, t: f64) -> Rect {
+        let lerp = |a: f64, b: f64| a + (b - a) * t;
+        Rect::new(
+            lerp(self.x, other.x),
+            lerp(self.y, other.y),
+            lerp(self.width, other.width),
+            lerp(self.height, other.height),
+        )
+    }
+
+    /// 面積を持たないかどうか
+    pub fn is_empty(&self) -> bool {
+        self.width <= 0.0 || self.height <= 0.0
+    }
+
+    /// 範囲内に収めた矩形．範囲と重ならない場合はNone
+    pub fn clamp_to(&self, bounds: &Rect) -> Option<Rect> {
+        let x = self.x.max(bounds.x);
+        let y = self.y.max(bounds.y);
+        let clamped = Rect::new(
+            x,
+            y,
+            self.right().min(bounds.right()) - x,
+            self.bottom().min(bounds.bottom()) - y,
+        );
+        (!clamped.is_empty()).then_some(clamped)
+    }
+}
+
+impl From<&rustface::Rectangle> for Rect {
+    fn from(rectangle: &rustface::Rectangle) -> Self {
+        Self::new(
+            rectangle.x() as f64,
+            rectangle.y() as f64,
+            rectangle.width() as f64,
+            rectangle.height() as f64,
+        )
+    }
+}
+
+/// CSSのobject-fitに対応する，要素の中への内容の配置方法
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum ObjectFit {
+    /// 縦横比を無視して引き伸ばす
+    Fill,
+    /// 縦横比を保って全体が収まるように配置する(レターボックス)
+    #[default]
+    Contain,
+    /// 縦横比を保って要素を覆うように配置する(はみ出した部分は切り取られる)
+    Cover,
+}
+
+impl ObjectFit {
+    /// CSSのobject-fitの値
+    pub fn as_css(&self) -> &'static str {
+        match self {
+            ObjectFit::Fill => "fill",
+            ObjectFit::Contain => "contain",
+            ObjectFit::Cover => "cover",
+        }
+    }
+
+    /// CSSのobject-fitの値から変換．不明な値はcontainとする
+    pub fn from_css(value: &str) -> Self {
+        match value {
+            "fill" => ObjectFit::Fill,
+            "cover" => ObjectFit::Cover,
+            _ => ObjectFit::Contain,
+        }
+    }
+}
+
+/// 軸ごとの拡大縮小と平行移動からなる座標変換．x' = scale_x * x + offset_x (yも同様)．
+/// scale_xが負の場合は左右反転を表す
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub scale_x: f64,
+    pub scale_y: f64,
+    pub offset_x: f64,
+    pub offset_y: f64,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Transform {
+    /// 恒等変換
+    pub fn identity() -> Self {
+        Self::scale(1.0, 1.0)
+    }
+
+    /// 拡大縮小
+    pub fn scale(scale_x: f64, scale_y: f64) -> Self {
+        Self {
+            scale_x,
+            scale_y,
+            offset_x: 0.0,
+            offset_y: 0.0,
+        }
+    }
+
+    /// 幅widthの領域内での左右反転
+    pub fn mirror_x(width: f64) -> Self {
+        Self {
+            scale_x: -1.0,
+            offset_x: width,
+            ..Self::identity()
+        }
+    }
+
+    /// source(内容)をtarget(要素)の中央にobject-fitで配置する変換
+    pub fn fit(source: Size, target: Size, object_fit: ObjectFit) -> Self {
+        let (scale_x, scale_y) = (target.width / source.width, target.height / source.height);
+        let (scale_x, scale_y) = match object_fit {
+            ObjectFit::Fill => (scale_x, scale_y),
+            ObjectFit::Contain => (scale_x.min(scale_y), scale_x.min(scale_y)),
+            ObjectFit::Cover => (scale_x.max(scale_y), scale_x.max(scale_y)),
+        };
+        Self {
+            scale_x,
+            scale_y,
+            offset_x: (target.width - source.width * scale_x) / 2.0,
+            offset_y: (target.height - source.height * scale_y) / 2.0,
+        }
+    }
+
+    /// selfの後にnextを適用する変換
+    pub fn then(&self, next: &Transform) -> Self {
+        Self {
+            scale_x: next.scale_x * self.scale_x,
+            scale_y: next.scale_y * self.scale_y,
+            offset_x: next.scale_x * self.offset_x + next.offset_x,
+            offset_y: next.scale_y * self.offset_y + next.offset_y,
+        }
+    }
+
+    /// 左右反転を含むかどうか
+    pub fn is_mirrored(&self) -> bool {
+        (self.scale_x < 0.0) != (self.scale_y < 0.0)
+    }
+
+    /// 点を変換
+    pub fn apply_point(&self, x: f64, y: f64) -> (f64, f64) {
+        (
+            self.scale_x * x + self.offset_x,
+            self.scale_y * y + self.offset_y,
+        )
+    }
+
+    /// 矩形を変換．反転した場合も幅と高さが正になるように左上を取り直す
+    pub fn apply_rect(&self, rect: &Rect) -> Rect {
+        let (x0, y0) = self.apply_point(rect.x, rect.y);
+        let (x1, y1) = self.apply_point(rect.right(), rect.bottom());
+        Rect::new(x0.min(x1), y0.min(y1), (x1 - x0).abs(), (y1 - y0).abs())
+    }
+
+    /// 画像面内の回転角[rad](時計回りが正)を変換
+    pub fn apply_angle(&self, angle: f64) -> f64 {
+        if self.is_mirrored() {
+            -angle
+        } else {
+            angle
+        }
+    }
+
+    /// 長さの拡大率．縦横で異なる場合(Fill)は両方向の拡大率の相乗平均とする
+    pub fn length_scale(&self) -> f64 {
+        (self.scale_x.abs() * self.scale_y.abs()).sqrt()
+    }
+
+    /// キャンバスのコンテキストに変換を設定する(描画前に呼ぶ)．元に戻す場合はsave/restoreを使う
+    pub fn apply_to_context(
+        &self,
+        context: &web_sys::CanvasRenderingContext2d,
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        context.transform(
+            self.scale_x,
+            0.0,
+            0.0,
+            self.scale_y,
+            self.offset_x,
+            self.offset_y,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_rect_eq(actual: Rect, expected: Rect) {
+        let eq = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        assert!(
+            eq(actual.x, expected.x)
+                && eq(actual.y, expected.y)
+                && eq(actual.width, expected.width)
+                && eq(actual.height, expected.height),
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn negative_rectangle_is_preserved() {
+        let rect = Rect::from(&rustface::Rectangle::new(-10, -5, 40, 30));
+        assert_rect_eq(rect, Rect::new(-10.0, -5.0, 40.0, 30.0));
+    }
+
+    #[test]
+    fn clamp_to_bounds() {
+        let bounds = Rect::new(0.0, 0.0, 100.0, 50.0);
+        assert_rect_eq(
+            Rect::new(-10.0, 40.0, 30.0, 30.0)
+                .clamp_to(&bounds)
+                .unwrap(),
+            Rect::new(0.0, 40.0, 20.0, 10.0),
+        );
+        assert_eq!(Rect::new(120.0, 0.0, 10.0, 10.0).clamp_to(&bounds), None);
+    }
+
+    #[test]
+    fn scale_rect() {
+        let transform = Transform::scale(2.0, 3.0);
+        assert_rect_eq(
+            transform.apply_rect(&Rect::new(1.0, 2.0, 3.0, 4.0)),
+            Rect::new(2.0, 6.0, 6.0, 12.0),
+        );
+    }
+
+    #[test]
+    fn fit_contain_letterboxes() {
+        // 4:3の内容を16:9の要素に配置すると左右に余白ができる
+        let transform = Transform::fit(
+            Size::new(640.0, 480.0),
+            Size::new(1600.0, 900.0),
+            ObjectFit::Contain,
+        );
+        assert_rect_eq(
+            transform.apply_rect(&Rect::new(0.0, 0.0, 640.0, 480.0)),
+            Rect::new(200.0, 0.0, 1200.0, 900.0),
+        );
+    }
+
+    #[test]
+    fn fit_cover_crops() {
+        // 4:3の内容を16:9の要素に配置すると上下がはみ出す
+        let transform = Transform::fit(
+            Size::new(640.0, 480.0),
+            Size::new(1600.0, 900.0),
+            ObjectFit::Cover,
+        );
+        assert_rect_eq(
+            transform.apply_rect(&Rect::new(0.0, 0.0, 640.0, 480.0)),
+            Rect::new(0.0, -150.0, 1600.0, 1200.0),
+        );
+    }
+
+    #[test]
+    fn fit_fill_stretches() {
+        let transform = Transform::fit(
+            Size::new(100.0, 100.0),
+            Size::new(200.0, 50.0),
+            ObjectFit::Fill,
+        );
+        assert_rect_eq(
+            transform.apply_rect(&Rect::new(10.0, 10.0, 10.0, 10.0)),
+            Rect::new(20.0, 5.0, 20.0, 5.0),
+        );
+        // x方向に2倍，y方向に0.5倍なので長さの拡大率は1倍
+        assert!((transform.length_scale() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn length_scale_ignores_mirroring() {
+        let transform = Transform::fit(
+            Size::new(100.0, 50.0),
+            Size::new(300.0, 300.0),
+            ObjectFit::Contain,
+        )
+        .then(&Transform::mirror_x(300.0));
+        assert!((transform.length_scale() - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn mirror_rect_and_angle() {
+        let transform = Transform::mirror_x(100.0);
+        assert_rect_eq(
+            transform.apply_rect(&Rect::new(10.0, 20.0, 30.0, 40.0)),
+            Rect::new(60.0, 20.0, 30.0, 40.0),
+        );
+        assert!(transform.is_mirrored());
+        assert_eq!(transform.apply_angle(0.5), -0.5);
+    }
+
+    #[test]
+    fn then_composes_in_order() {
+        // 2倍してから幅200で反転
+        let transform = Transform::scale(2.0, 2.0).then(&Transform::mirror_x(200.0));
+        assert_eq!(transform.apply_point(10.0, 10.0), (180.0, 20.0));
+        assert_rect_eq(
+            transform.apply_rect(&Rect::new(10.0, 10.0, 20.0, 20.0)),
+            Rect::new(140.0, 20.0, 40.0, 40.0),
+        );
+    }
+}
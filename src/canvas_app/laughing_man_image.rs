@@ -1,113 +1,649 @@
-use super::dom_utils::document;
+use super::dom_utils::{document, load_image};
+use super::geometry::{Rect, Size};
+use super::overlay_assignment::{OverlayAssigner, OverlayAssignment};
+use super::overlay_registry::Overlay;
 use crate::error::AppError;
 
+use std::cell::Cell;
 use wasm_bindgen::JsCast;
 
+/// 表示用のキャンバス(.canvas-app)のz-index
+const CANVAS_Z_INDEX: u32 = 3;
+
+/// 笑い男画像の描画方法
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum OverlayRenderer {
+    /// 顔ごとにimg要素を配置する
+    #[default]
+    Dom,
+    /// 表示用のキャンバスに描画する．録画やスナップショットに含まれる
+    Canvas,
+}
+
+impl OverlayRenderer {
+    /// セレクトボックスの値に変換
+    pub fn to_value(self) -> &'static str {
+        match self {
+            OverlayRenderer::Dom => "dom",
+            OverlayRenderer::Canvas => "canvas",
+        }
+    }
+
+    /// セレクトボックスの値から変換
+    pub fn from_value(value: &str) -> Self {
+        match value {
+            "canvas" => OverlayRenderer::Canvas,
+            _ => OverlayRenderer::Dom,
+        }
+    }
+}
+
+/// 笑い男画像(DOM)が現れる・消える時のアニメーション
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum OverlayTransition {
+    /// アニメーションしない
+    None,
+    /// 不透明度を変える
+    #[default]
+    Fade,
+    /// 中心から拡大・縮小する
+    Pop,
+}
+
+impl OverlayTransition {
+    /// セレクトボックスの値に変換
+    pub fn to_value(self) -> &'static str {
+        match self {
+            OverlayTransition::None => "none",
+            OverlayTransition::Fade => "fade",
+            OverlayTransition::Pop => "pop",
+        }
+    }
+
+    /// セレクトボックスの値から変換
+    pub fn from_value(value: &str) -> Self {
+        match value {
+            "none" => OverlayTransition::None,
+            "pop" => OverlayTransition::Pop,
+            _ => OverlayTransition::Fade,
+        }
+    }
+
+    /// アニメーションするCSSのプロパティと，見える状態・見えない状態の値
+    fn css_property(self) -> Option<(&'static str, &'static str, &'static str)> {
+        match self {
+            OverlayTransition::None => None,
+            OverlayTransition::Fade => Some(("opacity", "1", "0")),
+            OverlayTransition::Pop => Some(("transform", "scale(1)", "scale(0)")),
+        }
+    }
+
+    /// CSSのtransitionの値．現れる場合の拡大は少し行き過ぎてから戻す
+    fn css_transition(self, duration: f64, is_appearing: bool) -> Option<String> {
+        let (property, _, _) = self.css_property()?;
+        let timing_function = match (self, is_appearing) {
+            (OverlayTransition::Pop, true) => "cubic-bezier(0.34, 1.56, 0.64, 1)",
+            (_, true) => "ease-out",
+            (_, false) => "ease-in",
+        };
+        Some(format!("{property} {duration}ms {timing_function}"))
+    }
+}
+
+/// 重ねる画像の縦横比の扱い
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum OverlayAspect {
+    /// 縦横比を保ち，笑い男画像のオプションの合わせ方(OverlayFit)で顔に合わせる
+    #[default]
+    Keep,
+    /// 顔の矩形に合わせて引き伸ばす
+    Stretch,
+}
+
+impl OverlayAspect {
+    /// セレクトボックスの値に変換
+    pub fn to_value(self) -> &'static str {
+        match self {
+            OverlayAspect::Keep => "keep",
+            OverlayAspect::Stretch => "stretch",
+        }
+    }
+
+    /// セレクトボックスの値から変換
+    pub fn from_value(value: &str) -> Self {
+        match value {
+            "stretch" => OverlayAspect::Stretch,
+            _ => OverlayAspect::Keep,
+        }
+    }
+}
+
+/// 縦横比を保つ場合の，笑い男画像の大きさの顔の矩形への合わせ方
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum OverlayFit {
+    /// 高さを合わせる
+    #[default]
+    FitHeight,
+    /// 幅を合わせる
+    FitWidth,
+    /// 顔の矩形を覆う
+    Cover,
+    /// 顔の矩形に収める
+    Contain,
+}
+
+impl OverlayFit {
+    /// セレクトボックスの値に変換
+    pub fn to_value(self) -> &'static str {
+        match self {
+            OverlayFit::FitHeight => "fit-height",
+            OverlayFit::FitWidth => "fit-width",
+            OverlayFit::Cover => "cover",
+            OverlayFit::Contain => "contain",
+        }
+    }
+
+    /// セレクトボックスの値から変換
+    pub fn from_value(value: &str) -> Self {
+        match value {
+            "fit-width" => OverlayFit::FitWidth,
+            "cover" => OverlayFit::Cover,
+            "contain" => OverlayFit::Contain,
+            _ => OverlayFit::FitHeight,
+        }
+    }
+
+    /// 縦横比を保ったまま画像(image_size)をtargetに合わせた大きさ
+    fn fit(self, image_size: Size, target: Size) -> Size {
+        let (scale_x, scale_y) = (
+            target.width / image_size.width,
+            target.height / image_size.height,
+        );
+        let scale = match self {
+            OverlayFit::FitHeight => scale_y,
+            OverlayFit::FitWidth => scale_x,
+            OverlayFit::Cover => scale_x.max(scale_y),
+            OverlayFit::Contain => scale_x.min(scale_y),
+        };
+        Size::new(image_size.width * scale, image_size.height * scale)
+    }
+}
+
+/// 重ねて表示する笑い男画像の各層．全ての層は同じサイズの画像とする
+#[derive(Clone, Debug, PartialEq)]
+pub struct OverlayLayers {
+    /// 回転する層(外周の文字)の画像のURL．Noneの場合は回転しない
+    pub ring_url: Option<String>,
+    /// 回転しない層(顔)の画像のURL
+    pub face_url: String,
+    /// 回転の中心(画像の幅・高さに対する比率)
+    pub ring_center: (f64, f64),
+    /// 顔の中心に合わせる点(画像の幅・高さに対する比率)
+    pub anchor: (f64, f64),
+    /// 縦横比の扱い
+    pub aspect: OverlayAspect,
+}
+
+impl Default for OverlayLayers {
+    fn default() -> Self {
+        Self {
+            ring_url: Some("/laughing-man-ring.svg".to_string()),
+            face_url: "/laughing-man-face.svg".to_string(),
+            // viewBox="-160 -160 360 320"の原点(顔の中心)
+            ring_center: (160.0 / 360.0, 0.5),
+            anchor: (160.0 / 360.0, 0.5),
+            aspect: OverlayAspect::Keep,
+        }
+    }
+}
+
 /// 笑い男画像のオプション
 #[derive(Clone, Debug)]
 pub struct LaughingManOptions {
     pub laughing_man_size_ratio: f64,
-    pub laughing_man_shift_ratio: f64,
+    /// 顔の中心から右へずらす量(顔の幅に対する比率)
+    pub laughing_man_shift_x_ratio: f64,
+    /// 顔の中心から下へずらす量(顔の高さに対する比率)
+    pub laughing_man_shift_y_ratio: f64,
+    /// 縦横比を保つ場合の顔の矩形への合わせ方
+    pub fit: OverlayFit,
+    /// DOMの場合はimg要素のz-index．キャンバスの場合はキャンバスのz-indexより大きければ他の描画の上に描く
     pub laughing_man_z_index: u32,
+    /// 描画方法
+    pub renderer: OverlayRenderer,
+    /// 外周の文字の回転速度[deg/s]．時計回りが正
+    pub ring_rotation_speed: f64,
+    /// トラックへの画像の割り当て方法
+    pub assignment: OverlayAssignment,
+    /// トラッカーの更新の間も描画ループで位置を外挿して滑らかに動かすかどうか．
+    /// 笑い男画像(DOM・キャンバス)，匿名化，枠の表示の全てに適用する
+    pub smooth_motion: bool,
+    /// 顔が現れた時のアニメーション(DOMの場合)
+    pub appear_transition: OverlayTransition,
+    /// 顔が消えた時のアニメーション(DOMの場合)．終わるまで画像を残す
+    pub disappear_transition: OverlayTransition,
+    /// 現れる・消えるアニメーションの時間[ms]
+    pub transition_duration: f64,
 }
 
 impl Default for LaughingManOptions {
     fn default() -> Self {
         LaughingManOptions {
             laughing_man_size_ratio: 1.0,
-            laughing_man_shift_ratio: 0.0,
+            laughing_man_shift_x_ratio: 0.0,
+            laughing_man_shift_y_ratio: 0.0,
+            fit: Default::default(),
             laughing_man_z_index: 4,
+            renderer: Default::default(),
+            ring_rotation_speed: -36.0,
+            assignment: Default::default(),
+            smooth_motion: true,
+            appear_transition: Default::default(),
+            disappear_transition: Default::default(),
+            transition_duration: 200.0,
+        }
+    }
+}
+
+impl LaughingManOptions {
+    /// キャンバスに描画する場合に，キャンバスの他の描画(情報の文字など)より上に描くかどうか
+    pub fn is_above_canvas(&self) -> bool {
+        self.laughing_man_z_index > CANVAS_Z_INDEX
+    }
+
+    /// トラックごとの外周の文字の初期位相[deg]．重なった笑い男画像が同じ見た目にならないように黄金角ずつずらす
+    pub fn ring_phase(&self, id: u32) -> f64 {
+        (id as f64 * 137.5) % 360.0
+    }
+
+    /// 時刻time[s]における外周の文字の回転角[deg]
+    pub fn ring_angle(&self, id: u32, time: f64) -> f64 {
+        (self.ring_phase(id) + self.ring_rotation_speed * time) % 360.0
+    }
+
+    /// 笑い男画像を配置する矩形．画像の基準点を(シフトした)顔の中心に合わせ，顔の中心を基準に拡大する
+    /// - image_size: 画像のサイズ(縦横比を保つ場合に利用)
+    pub fn overlay_rect(
+        &self,
+        state: &LaughingManState,
+        layers: &OverlayLayers,
+        image_size: Size,
+    ) -> Rect {
+        let target = Size::new(
+            state.rect.width * self.laughing_man_size_ratio,
+            state.rect.height * self.laughing_man_size_ratio,
+        );
+        let Size { width, height } = match layers.aspect {
+            OverlayAspect::Keep => self.fit.fit(image_size, target),
+            OverlayAspect::Stretch => target,
+        };
+        let anchor_x = state.rect.center_x() + self.laughing_man_shift_x_ratio * state.rect.width;
+        let anchor_y = state.rect.center_y() + self.laughing_man_shift_y_ratio * state.rect.height;
+        Rect::new(
+            anchor_x - width * layers.anchor.0,
+            anchor_y - height * layers.anchor.1,
+            width,
+            height,
+        )
+    }
+}
+
+/// キャンバスに笑い男画像を描画する．画像は事前に読み込んでおく
+pub struct LaughingManCanvasRenderer {
+    /// 回転する層の画像
+    ring_image: Option<web_sys::HtmlImageElement>,
+    /// 回転しない層の画像
+    face_image: web_sys::HtmlImageElement,
+    /// 読み込んだ層
+    layers: OverlayLayers,
+}
+
+impl LaughingManCanvasRenderer {
+    /// 笑い男画像の各層を読み込む
+    pub async fn load(layers: &OverlayLayers) -> Result<Self, AppError> {
+        let ring_image = match layers.ring_url.as_ref() {
+            Some(ring_url) => Some(load_image(ring_url).await?),
+            None => None,
+        };
+        Ok(Self {
+            ring_image,
+            face_image: load_image(&layers.face_url).await?,
+            layers: layers.clone(),
+        })
+    }
+
+    /// 画像の集合を全て読み込む
+    pub async fn load_all(layer_set: &[OverlayLayers]) -> Result<Vec<Self>, AppError> {
+        let mut renderers = Vec::with_capacity(layer_set.len());
+        for layers in layer_set {
+            renderers.push(Self::load(layers).await?);
+        }
+        Ok(renderers)
+    }
+
+    /// 読み込んだ層．DOMの笑い男画像も同じ画像を使う
+    pub fn layers(&self) -> &OverlayLayers {
+        &self.layers
+    }
+
+    /// 画像のサイズ
+    pub fn image_size(&self) -> Size {
+        Size::new(
+            self.face_image.natural_width() as f64,
+            self.face_image.natural_height() as f64,
+        )
+    }
+
+    /// 一つの顔に笑い男画像を描画する
+    /// - time: 回転の基準となる時刻[s]
+    pub fn draw_state(
+        &self,
+        context: &web_sys::CanvasRenderingContext2d,
+        state: &LaughingManState,
+        options: &LaughingManOptions,
+        time: f64,
+    ) -> Result<(), AppError> {
+        let overlay_rect = options.overlay_rect(state, &self.layers, self.image_size());
+
+        if let Some(ring_image) = self.ring_image.as_ref() {
+            let (center_x, center_y) = (
+                overlay_rect.x + overlay_rect.width * self.layers.ring_center.0,
+                overlay_rect.y + overlay_rect.height * self.layers.ring_center.1,
+            );
+            let angle = options.ring_angle(state.id, time).to_radians();
+
+            context.save();
+            let draw_res = context
+                .translate(center_x, center_y)
+                .and_then(|_| context.rotate(angle))
+                .and_then(|_| {
+                    context.draw_image_with_html_image_element_and_dw_and_dh(
+                        ring_image,
+                        overlay_rect.x - center_x,
+                        overlay_rect.y - center_y,
+                        overlay_rect.width,
+                        overlay_rect.height,
+                    )
+                });
+            context.restore();
+            draw_res?;
         }
+
+        context.draw_image_with_html_image_element_and_dw_and_dh(
+            &self.face_image,
+            overlay_rect.x,
+            overlay_rect.y,
+            overlay_rect.width,
+            overlay_rect.height,
+        )?;
+        Ok(())
     }
 }
 
+/// 全ての顔にトラックごとに割り当てた画像を描画する．後の顔ほど上に描かれる(DOMで後から追加した要素と同じ)
+pub fn draw_overlays(
+    context: &web_sys::CanvasRenderingContext2d,
+    renderers: &[LaughingManCanvasRenderer],
+    assigner: &mut OverlayAssigner,
+    states: &[LaughingManState],
+    options: &LaughingManOptions,
+    time: f64,
+) -> Result<(), AppError> {
+    for state in states {
+        let index = assigner.assign(state.id, renderers.len(), options.assignment);
+        if let Some(renderer) = renderers.get(index) {
+            renderer.draw_state(context, state, options, time)?;
+        }
+    }
+    Ok(())
+}
+
 /// 笑い男画像で使用する状態
 #[derive(Clone, Debug)]
 pub struct LaughingManState {
     pub id: u32,
-    pub left: u32,
-    pub top: u32,
-    pub width: u32,
-    pub height: u32,
+    /// 顔の矩形(表示の座標)．画面外にはみ出す場合は負の座標になる
+    pub rect: Rect,
 }
 
-/// Signalのネストや多用を防ぐため，直接DOMを操作する笑い男画像
+/// img要素を作成するヘルパー関数
+fn create_img_element(src_url: &str) -> Result<web_sys::HtmlImageElement, AppError> {
+    let img_element = document()
+        .create_element("img")?
+        .dyn_into::<web_sys::HtmlImageElement>()
+        .map_err(|element| {
+            AppError::DomError(format!(
+                "Cannot convert from: {element:?} into {}",
+                std::any::type_name::<web_sys::HtmlImageElement>()
+            ))
+        })?;
+    img_element
+        .style()
+        .set_css_text("position:absolute;top:0px;left:0px");
+    img_element.set_src(src_url);
+    Ok(img_element)
+}
+
+/// Signalのネストや多用を防ぐため，直接DOMを操作する笑い男画像．
+/// 各層のimg要素をdiv要素にまとめ，回転する層はCSSアニメーションで回す．
+/// 現れる・消えるアニメーションはdiv要素のCSSトランジションで行う
 pub struct LaughingManImage {
-    img_element: web_sys::HtmlImageElement,
+    container_element: web_sys::HtmlElement,
+    img_elements: Vec<web_sys::HtmlImageElement>,
+    layers: OverlayLayers,
+    image_size: Size,
     options: LaughingManOptions,
-    id: u32,
+    /// 最後に重ねた顔の矩形(表示の座標)
+    face_rect: Cell<Rect>,
+    /// 消えるアニメーションを始めた時刻[ms]
+    leave_started_at: Option<f64>,
 }
 
 impl LaughingManImage {
+    /// 画像はrendererで読み込んだものと同じURLを使う
     pub fn new(
         parent_node: web_sys::Node,
-        src_url: &str,
+        renderer: &LaughingManCanvasRenderer,
         options: LaughingManOptions,
         initial_state: LaughingManState,
     ) -> Result<Self, AppError> {
-        let img_element = document()
-            .create_element("img")?
-            .dyn_into::<web_sys::HtmlImageElement>()
+        let container_element = document()
+            .create_element("div")?
+            .dyn_into::<web_sys::HtmlElement>()
             .map_err(|element| {
                 AppError::DomError(format!(
                     "Cannot convert from: {element:?} into {}",
-                    std::any::type_name::<web_sys::HtmlImageElement>()
+                    std::any::type_name::<web_sys::HtmlElement>()
                 ))
             })?;
-        img_element.set_id(&format!("laughing-man-img-{}", initial_state.id));
-        img_element
-            .set_height(((initial_state.height as f64) * options.laughing_man_size_ratio) as u32); // 高さのみ合わせる
-
-        let img_style = img_element.style();
-        img_style.set_css_text(&format!(
-            "position:absolute;top:{}px;left:{}px;z-index:{}",
-            (options.laughing_man_shift_ratio * (initial_state.height as f64)
-                + (initial_state.top as f64)) as u32,
-            (options.laughing_man_shift_ratio * (initial_state.width as f64)
-                + (initial_state.left as f64)) as u32,
+        container_element.set_id(&format!("laughing-man-img-{}", initial_state.id));
+        container_element.style().set_css_text(&format!(
+            "position:absolute;z-index:{}",
             options.laughing_man_z_index
         ));
-        img_element.set_src(src_url);
 
-        parent_node.append_child(img_element.as_ref())?;
+        let layers = renderer.layers();
+        let mut img_elements = Vec::new();
+        if let Some(ring_url) = layers.ring_url.as_ref() {
+            let ring_element = create_img_element(ring_url)?;
+            let ring_style = ring_element.style();
+            ring_style.set_property(
+                "transform-origin",
+                &format!(
+                    "{}% {}%",
+                    layers.ring_center.0 * 100.0,
+                    layers.ring_center.1 * 100.0
+                ),
+            )?;
+            // 回転の位相はアニメーションの開始位置(負の遅延)で表す
+            if options.ring_rotation_speed != 0.0 {
+                let period = 360.0 / options.ring_rotation_speed.abs();
+                let phase_ratio = options.ring_phase(initial_state.id) / 360.0;
+                let (direction, progress) = if options.ring_rotation_speed > 0.0 {
+                    ("normal", phase_ratio)
+                } else {
+                    ("reverse", 1.0 - phase_ratio)
+                };
+                ring_style.set_property(
+                    "animation",
+                    &format!(
+                        "laughing-man-ring-rotate {period}s linear {}s infinite {direction}",
+                        -period * progress
+                    ),
+                )?;
+            } else {
+                ring_style.set_property(
+                    "transform",
+                    &format!("rotate({}deg)", options.ring_phase(initial_state.id)),
+                )?;
+            }
+            img_elements.push(ring_element);
+        }
+        img_elements.push(create_img_element(&layers.face_url)?);
 
-        Ok(Self {
-            img_element,
+        for img_element in img_elements.iter() {
+            container_element.append_child(img_element.as_ref())?;
+        }
+        parent_node.append_child(container_element.as_ref())?;
+
+        let laughing_man_image = Self {
+            container_element,
+            img_elements,
+            layers: layers.clone(),
+            image_size: renderer.image_size(),
             options,
-            id: initial_state.id,
-        })
+            face_rect: Cell::new(initial_state.rect),
+            leave_started_at: None,
+        };
+        laughing_man_image.step(initial_state)?;
+        laughing_man_image.appear()?;
+        Ok(laughing_man_image)
+    }
+
+    /// 見えない状態から現れるアニメーションを始める
+    fn appear(&self) -> Result<(), AppError> {
+        let Some((property, _, hidden)) = self.options.appear_transition.css_property() else {
+            return Ok(());
+        };
+        self.container_element
+            .style()
+            .set_property(property, hidden)?;
+        // 見えない状態を反映させてからトランジションを設定する(レイアウトを強制する)
+        self.container_element.offset_width();
+        self.transition_to(self.options.appear_transition, true)
+    }
+
+    /// トランジションを設定し，見える状態または見えない状態に変える
+    fn transition_to(&self, transition: OverlayTransition, is_shown: bool) -> Result<(), AppError> {
+        if let (Some((property, shown, hidden)), Some(css_transition)) = (
+            transition.css_property(),
+            transition.css_transition(self.options.transition_duration, is_shown),
+        ) {
+            let container_style = self.container_element.style();
+            container_style.set_property("transition", &css_transition)?;
+            container_style.set_property(property, if is_shown { shown } else { hidden })?;
+        }
+        Ok(())
     }
 
     pub fn step(&self, state: LaughingManState) -> Result<(), AppError> {
-        self.img_element
-            .set_height(((state.height as f64) * self.options.laughing_man_size_ratio) as u32); // 高さのみ合わせる
-        let img_style = self.img_element.style();
-        img_style.set_property(
-            "top",
-            &format!(
-                "{}px",
-                (self.options.laughing_man_shift_ratio * (state.height as f64) + (state.top as f64))
-                    as u32
-            ),
-        )?;
-        img_style.set_property(
-            "left",
-            &format!(
-                "{}px",
-                (self.options.laughing_man_shift_ratio * (state.width as f64) + (state.left as f64))
-                    as u32
-            ),
-        )?;
+        self.face_rect.set(state.rect);
+        let overlay_rect = self
+            .options
+            .overlay_rect(&state, &self.layers, self.image_size);
+        for img_element in self.img_elements.iter() {
+            img_element.set_width(overlay_rect.width.max(0.0) as u32);
+            img_element.set_height(overlay_rect.height.max(0.0) as u32);
+        }
+        let container_style = self.container_element.style();
+        container_style.set_property("top", &format!("{}px", overlay_rect.y))?;
+        container_style.set_property("left", &format!("{}px", overlay_rect.x))?;
         Ok(())
     }
+}
+
+impl Overlay for LaughingManImage {
+    fn begin_leave(&mut self, time: f64) {
+        self.leave_started_at = Some(time);
+        if let Err(e) = self.transition_to(self.options.disappear_transition, false) {
+            log::error!("{e}");
+        }
+    }
+
+    /// 途中の状態から見える状態に戻す
+    fn cancel_leave(&mut self) {
+        self.leave_started_at = None;
+        if let Err(e) = self.transition_to(self.options.disappear_transition, true) {
+            log::error!("{e}");
+        }
+    }
 
-    pub fn id(&self) -> u32 {
-        self.id
+    fn has_left(&self, time: f64) -> bool {
+        self.leave_started_at.is_some_and(|leave_started_at| {
+            self.options.disappear_transition == OverlayTransition::None
+                || time - leave_started_at >= self.options.transition_duration
+        })
+    }
+
+    fn face_rect(&self) -> Rect {
+        self.face_rect.get()
     }
 }
 
 impl Drop for LaughingManImage {
     fn drop(&mut self) {
-        self.img_element.remove();
+        self.container_element.remove();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 中心を基準点とする横長(2:1)の画像を顔の矩形に重ねた矩形
+    fn overlay_rect(options: &LaughingManOptions, aspect: OverlayAspect, face: Rect) -> Rect {
+        let layers = OverlayLayers {
+            anchor: (0.5, 0.5),
+            aspect,
+            ..Default::default()
+        };
+        let state = LaughingManState { id: 0, rect: face };
+        options.overlay_rect(&state, &layers, Size::new(40.0, 20.0))
+    }
+
+    #[test]
+    fn fit_modes_keep_aspect() {
+        // 縦長の顔(50x100)に横長の画像を合わせる
+        let size_of = |fit| {
+            let options = LaughingManOptions {
+                fit,
+                ..Default::default()
+            };
+            let rect = overlay_rect(
+                &options,
+                OverlayAspect::Keep,
+                Rect::new(0.0, 0.0, 50.0, 100.0),
+            );
+            (rect.width, rect.height)
+        };
+        assert_eq!(size_of(OverlayFit::FitHeight), (200.0, 100.0));
+        assert_eq!(size_of(OverlayFit::FitWidth), (50.0, 25.0));
+        assert_eq!(size_of(OverlayFit::Cover), (200.0, 100.0));
+        assert_eq!(size_of(OverlayFit::Contain), (50.0, 25.0));
+    }
+
+    #[test]
+    fn shift_x_and_y_independently() {
+        let options = LaughingManOptions {
+            laughing_man_shift_y_ratio: -0.2,
+            ..Default::default()
+        };
+        let rect = overlay_rect(
+            &options,
+            OverlayAspect::Stretch,
+            Rect::new(0.0, 0.0, 100.0, 50.0),
+        );
+        // 顔の中心から上へ顔の高さの0.2倍だけずらし，横にはずらさない
+        assert_eq!(rect, Rect::new(0.0, -10.0, 100.0, 50.0));
     }
 }
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+
+/// トラックへの重ねる画像の割り当て方法
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum OverlayAssignment {
+    /// 現れた順に画像を順番に割り当てる
+    #[default]
+    RoundRobin,
+    /// ランダムに割り当てる
+    Random,
+    /// 最初の画像を割り当て，顔をクリックすると次の画像に切り替える
+    Manual,
+}
+
+impl OverlayAssignment {
+    /// セレクトボックスの値に変換
+    pub fn to_value(self) -> &'static str {
+        match self {
+            OverlayAssignment::RoundRobin => "round-robin",
+            OverlayAssignment::Random => "random",
+            OverlayAssignment::Manual => "manual",
+        }
+    }
+
+    /// セレクトボックスの値から変換
+    pub fn from_value(value: &str) -> Self {
+        match value {
+            "random" => OverlayAssignment::Random,
+            "manual" => OverlayAssignment::Manual,
+            _ => OverlayAssignment::RoundRobin,
+        }
+    }
+}
+
+/// トラックIDごとに割り当てた画像の番号．トラックが削除されるまで同じ画像を使う
+#[derive(Debug, Default)]
+pub struct OverlayAssigner {
+    assignments: HashMap<u32, usize>,
+    /// 順番に割り当てる場合の次の画像の番号
+    next_index: usize,
+}
+
+impl OverlayAssigner {
+    /// トラックに割り当てた画像の番号．未割り当ての場合は割り当て方法に従って割り当てる
+    pub fn assign(
+        &mut self,
+        id: u32,
+        overlay_count: usize,
+        assignment: OverlayAssignment,
+    ) -> usize {
+        self.assign_with(id, overlay_count, assignment, js_sys::Math::random)
+    }
+
+    /// assignと同じ．ランダムに割り当てる場合はrandom([0, 1)の乱数)を使う
+    pub fn assign_with(
+        &mut self,
+        id: u32,
+        overlay_count: usize,
+        assignment: OverlayAssignment,
+        random: impl FnOnce() -> f64,
+    ) -> usize {
+        let overlay_count = overlay_count.max(1);
+        let index = *self
+            .assignments
+            .entry(id)
+            .or_insert_with(|| match assignment {
+                OverlayAssignment::RoundRobin => {
+                    let index = self.next_index % overlay_count;
+                    self.next_index = index + 1;
+                    index
+                }
+                OverlayAssignment::Random => {
+                    ((random() * overlay_count as f64) as usize).min(overlay_count - 1)
+                }
+                OverlayAssignment::Manual => 0,
+            });
+        // 画像の数が減った場合も範囲内に収める
+        index % overlay_count
+    }
+
+    /// 手動でトラックの画像を次の画像に切り替える
+    pub fn cycle(&mut self, id: u32, overlay_count: usize) -> usize {
+        let overlay_count = overlay_count.max(1);
+        let index = self.assignments.entry(id).or_insert(0);
+        *index = (*index + 1) % overlay_count;
+        *index
+    }
+
+    /// 割り当てを別のトラックに移す(消えている途中の画像を新しいトラックに付け替えた場合)
+    pub fn transfer(&mut self, from: u32, to: u32) {
+        if let Some(index) = self.assignments.remove(&from) {
+            self.assignments.insert(to, index);
+        }
+    }
+
+    /// 削除されたトラックの割り当てを解除する
+    pub fn release(&mut self, id: u32) {
+        self.assignments.remove(&id);
+    }
+
+    /// 全ての割り当てを解除する(トラッカーの初期化時)
+    pub fn clear(&mut self) {
+        self.assignments.clear();
+        self.next_index = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assign(assigner: &mut OverlayAssigner, id: u32, assignment: OverlayAssignment) -> usize {
+        assigner.assign_with(id, 3, assignment, || panic!("random is not used"))
+    }
+
+    #[test]
+    fn round_robin_wraps_at_overlay_count() {
+        let mut assigner = OverlayAssigner::default();
+        let indices = (1..=5)
+            .map(|id| assign(&mut assigner, id, OverlayAssignment::RoundRobin))
+            .collect::<Vec<_>>();
+        assert_eq!(indices, vec![0, 1, 2, 0, 1]);
+
+        // 割り当て済みのトラックは同じ画像のまま
+        assert_eq!(assign(&mut assigner, 2, OverlayAssignment::RoundRobin), 1);
+        assert_eq!(assign(&mut assigner, 6, OverlayAssignment::RoundRobin), 2);
+    }
+
+    #[test]
+    fn random_uses_injected_source() {
+        let mut assigner = OverlayAssigner::default();
+        assert_eq!(
+            assigner.assign_with(1, 3, OverlayAssignment::Random, || 0.5),
+            1
+        );
+        assert_eq!(
+            assigner.assign_with(2, 3, OverlayAssignment::Random, || 0.999),
+            2
+        );
+        // 範囲外の値でも画像の数を超えない
+        assert_eq!(
+            assigner.assign_with(3, 3, OverlayAssignment::Random, || 1.0),
+            2
+        );
+        assert_eq!(assign(&mut assigner, 1, OverlayAssignment::Random), 1);
+    }
+
+    #[test]
+    fn cycle_advances_only_clicked_track() {
+        let mut assigner = OverlayAssigner::default();
+        assert_eq!(assign(&mut assigner, 1, OverlayAssignment::Manual), 0);
+        assert_eq!(assign(&mut assigner, 2, OverlayAssignment::Manual), 0);
+
+        assert_eq!(assigner.cycle(1, 3), 1);
+        assert_eq!(assigner.cycle(1, 3), 2);
+        assert_eq!(assigner.cycle(1, 3), 0);
+        assert_eq!(assigner.cycle(1, 3), 1);
+        assert_eq!(assign(&mut assigner, 1, OverlayAssignment::Manual), 1);
+        assert_eq!(assign(&mut assigner, 2, OverlayAssignment::Manual), 0);
+    }
+
+    #[test]
+    fn transfer_keeps_choice_on_rematch() {
+        let mut assigner = OverlayAssigner::default();
+        assign(&mut assigner, 1, OverlayAssignment::Manual);
+        assigner.cycle(1, 3);
+        assigner.cycle(1, 3);
+
+        assigner.transfer(1, 4);
+        assert_eq!(assign(&mut assigner, 4, OverlayAssignment::Manual), 2);
+        assert_eq!(assign(&mut assigner, 1, OverlayAssignment::Manual), 0);
+
+        // 割り当ての無いトラックからは移さない
+        assigner.transfer(9, 5);
+        assert_eq!(assign(&mut assigner, 5, OverlayAssignment::Manual), 0);
+    }
+
+    #[test]
+    fn release_and_clear_forget_tracks() {
+        let mut assigner = OverlayAssigner::default();
+        assert_eq!(assign(&mut assigner, 1, OverlayAssignment::RoundRobin), 0);
+        assert_eq!(assign(&mut assigner, 2, OverlayAssignment::RoundRobin), 1);
+
+        assigner.release(1);
+        assert_eq!(assign(&mut assigner, 1, OverlayAssignment::Manual), 0);
+        assigner.cycle(1, 3);
+        assigner.release(1);
+        assert_eq!(assign(&mut assigner, 1, OverlayAssignment::Manual), 0);
+
+        // 全て解除すると順番も最初からになる
+        assigner.clear();
+        assert_eq!(assign(&mut assigner, 2, OverlayAssignment::Manual), 0);
+        assert_eq!(assign(&mut assigner, 3, OverlayAssignment::RoundRobin), 0);
+    }
+}
@@ -0,0 +1,347 @@
+use super::geometry::Rect;
+
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+
+/// トラックに重ねる画像．トラックが削除されても消えるアニメーションが終わるまで残す
+pub trait Overlay {
+    /// 消えるアニメーションを始める．time[ms]はhas_leftと同じ基準の時刻
+    fn begin_leave(&mut self, time: f64);
+    /// 消えるアニメーションを取り消す(同じ顔が新しいトラックとして再び検出された場合)
+    fn cancel_leave(&mut self);
+    /// 消えるアニメーションが終わったかどうか
+    fn has_left(&self, time: f64) -> bool;
+    /// 最後に重ねた顔の矩形(表示の座標)
+    fn face_rect(&self) -> Rect;
+}
+
+/// トラックIDをキーとした重ねる画像の登録簿．
+/// トラッカーの追加・削除の結果と突き合わせ，roisの順番に依存せずにトラックと画像を対応させる．
+/// IDは追加順に増えるため，BTreeMapで古いトラックから順に並べる
+#[derive(Debug)]
+pub struct OverlayRegistry<T> {
+    overlays: BTreeMap<u32, T>,
+    /// 削除されたトラックの，消えるアニメーション中の画像
+    leaving: BTreeMap<u32, T>,
+}
+
+impl<T> Default for OverlayRegistry<T> {
+    fn default() -> Self {
+        Self {
+            overlays: BTreeMap::new(),
+            leaving: BTreeMap::new(),
+        }
+    }
+}
+
+/// reconcileの結果
+#[derive(Debug, PartialEq)]
+pub struct Reconciliation<E> {
+    /// 付け替えた(元のID, 新しいID)の組
+    pub rematched: Vec<(u32, u32)>,
+    /// 画像を作成できなかったIDとエラー．登録されないため，次のreconcileで再び作成を試みる
+    pub failed: Vec<(u32, E)>,
+}
+
+impl<T: Overlay> OverlayRegistry<T> {
+    /// 削除されたトラックの画像を消えるアニメーションに移し，tracksのうち未登録のトラックの画像を
+    /// createでIDと矩形(表示の座標)から作成する．既に登録されているIDは作り直さない．
+    /// トラッカーは作り直してもIDを再利用しないため，未登録のトラックの矩形に中心が含まれる
+    /// 消えるアニメーション中の画像があれば，アニメーションを取り消して新しいIDに付け替える
+    pub fn reconcile<E, F>(
+        &mut self,
+        tracks: &[(u32, Rect)],
+        removed_ids: &[u32],
+        time: f64,
+        mut create: F,
+    ) -> Reconciliation<E>
+    where
+        F: FnMut(u32, &Rect) -> Result<T, E>,
+    {
+        for id in removed_ids {
+            if let Some(mut overlay) = self.overlays.remove(id) {
+                overlay.begin_leave(time);
+                self.leaving.insert(*id, overlay);
+            }
+        }
+        let mut rematched = Vec::new();
+        let mut failed = Vec::new();
+        for (id, rect) in tracks {
+            debug_assert!(
+                !self.leaving.contains_key(id) || self.overlays.contains_key(id),
+                "track id {id} is reused while leaving"
+            );
+            if let Entry::Vacant(entry) = self.overlays.entry(*id) {
+                match take_nearest_leaving(&mut self.leaving, rect) {
+                    Some((leaving_id, mut overlay)) => {
+                        overlay.cancel_leave();
+                        rematched.push((leaving_id, *id));
+                        entry.insert(overlay);
+                    }
+                    None => match create(*id, rect) {
+                        Ok(overlay) => {
+                            entry.insert(overlay);
+                        }
+                        Err(e) => failed.push((*id, e)),
+                    },
+                }
+            }
+        }
+        Reconciliation { rematched, failed }
+    }
+
+    /// 消えるアニメーションが終わった画像を削除し，そのIDを返す
+    pub fn sweep(&mut self, time: f64) -> Vec<u32> {
+        let left_ids = self
+            .leaving
+            .iter()
+            .filter(|(_, overlay)| overlay.has_left(time))
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for id in left_ids.iter() {
+            self.leaving.remove(id);
+        }
+        left_ids
+    }
+
+    /// 登録されているIDが生存しているトラックのIDと一致するかどうか(消えるアニメーション中の画像は除く)
+    pub fn is_consistent_with(&self, live_ids: &[u32]) -> bool {
+        live_ids.len() == self.overlays.len()
+            && live_ids.iter().all(|id| self.overlays.contains_key(id))
+    }
+
+    pub fn get(&self, id: u32) -> Option<&T> {
+        self.overlays.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: u32) -> Option<&mut T> {
+        self.overlays.get_mut(&id)
+    }
+
+    /// 生存しているトラックIDと画像の組．古いトラックから順に返す
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &T)> {
+        self.overlays.iter().map(|(id, overlay)| (*id, overlay))
+    }
+
+    /// 消えるアニメーション中のものも含め，全ての画像をすぐに削除する
+    pub fn clear(&mut self) {
+        self.overlays.clear();
+        self.leaving.clear();
+    }
+}
+
+/// 消えるアニメーション中の画像のうち，顔の中心がrectに含まれ，rectの中心に最も近いものを取り出す
+fn take_nearest_leaving<T: Overlay>(
+    leaving: &mut BTreeMap<u32, T>,
+    rect: &Rect,
+) -> Option<(u32, T)> {
+    let distance = |overlay: &T| {
+        let face_rect = overlay.face_rect();
+        (face_rect.center_x() - rect.center_x()).hypot(face_rect.center_y() - rect.center_y())
+    };
+    let id = leaving
+        .iter()
+        .filter(|(_, overlay)| {
+            let face_rect = overlay.face_rect();
+            rect.contains(face_rect.center_x(), face_rect.center_y())
+        })
+        .min_by(|(_, a), (_, b)| distance(a).total_cmp(&distance(b)))
+        .map(|(id, _)| *id)?;
+    leaving.remove_entry(&id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 100[ms]で消えるテスト用の画像
+    #[derive(Debug, PartialEq)]
+    struct TestOverlay {
+        name: String,
+        rect: Rect,
+        leave_started_at: Option<f64>,
+    }
+
+    impl Overlay for TestOverlay {
+        fn begin_leave(&mut self, time: f64) {
+            self.leave_started_at = Some(time);
+        }
+
+        fn cancel_leave(&mut self) {
+            self.leave_started_at = None;
+        }
+
+        fn has_left(&self, time: f64) -> bool {
+            self.leave_started_at
+                .is_some_and(|leave_started_at| time - leave_started_at >= 100.0)
+        }
+
+        fn face_rect(&self) -> Rect {
+            self.rect
+        }
+    }
+
+    /// IDごとに重ならない位置の矩形
+    fn rect_of(id: u32) -> Rect {
+        Rect::new(id as f64 * 200.0, 0.0, 100.0, 100.0)
+    }
+
+    fn ids(registry: &OverlayRegistry<TestOverlay>) -> Vec<u32> {
+        registry.overlays.keys().copied().collect()
+    }
+
+    fn name(registry: &OverlayRegistry<TestOverlay>, id: u32) -> &str {
+        &registry.get(id).unwrap().name
+    }
+
+    fn reconcile_at(
+        registry: &mut OverlayRegistry<TestOverlay>,
+        added: &[(u32, Rect)],
+        removed_ids: &[u32],
+    ) -> Vec<(u32, u32)> {
+        let reconciliation = registry.reconcile(added, removed_ids, 0.0, |id, rect| {
+            Ok::<_, ()>(TestOverlay {
+                name: format!("overlay-{id}"),
+                rect: *rect,
+                leave_started_at: None,
+            })
+        });
+        assert!(reconciliation.failed.is_empty());
+        reconciliation.rematched
+    }
+
+    fn reconcile(
+        registry: &mut OverlayRegistry<TestOverlay>,
+        added_ids: &[u32],
+        removed_ids: &[u32],
+    ) -> Vec<(u32, u32)> {
+        let added = added_ids
+            .iter()
+            .map(|&id| (id, rect_of(id)))
+            .collect::<Vec<_>>();
+        reconcile_at(registry, &added, removed_ids)
+    }
+
+    #[test]
+    fn interleaved_additions_and_removals() {
+        let mut registry = OverlayRegistry::default();
+
+        reconcile(&mut registry, &[0, 1, 2], &[]);
+        assert!(registry.is_consistent_with(&[0, 1, 2]));
+
+        // 途中のトラックが削除され，新しいトラックが追加される
+        reconcile(&mut registry, &[3], &[1]);
+        assert_eq!(ids(&registry), vec![0, 2, 3]);
+        assert!(registry.is_consistent_with(&[0, 2, 3]));
+
+        // 先頭が削除されても残りのトラックの画像は変わらない
+        reconcile(&mut registry, &[4, 5], &[0]);
+        reconcile(&mut registry, &[], &[3]);
+        assert_eq!(ids(&registry), vec![2, 4, 5]);
+        for id in [2, 4, 5] {
+            assert_eq!(name(&registry, id), format!("overlay-{id}"));
+        }
+    }
+
+    #[test]
+    fn existing_overlay_is_not_recreated() {
+        let mut registry = OverlayRegistry::default();
+        reconcile(&mut registry, &[7], &[]);
+        registry.get_mut(7).unwrap().name = "manual".to_string();
+
+        reconcile(&mut registry, &[7], &[]);
+        assert_eq!(name(&registry, 7), "manual");
+    }
+
+    #[test]
+    fn unknown_removal_is_ignored() {
+        let mut registry = OverlayRegistry::default();
+        reconcile(&mut registry, &[1], &[9]);
+        assert_eq!(ids(&registry), vec![1]);
+    }
+
+    #[test]
+    fn inconsistency_is_detected() {
+        let mut registry = OverlayRegistry::default();
+        reconcile(&mut registry, &[1, 2], &[]);
+        assert!(!registry.is_consistent_with(&[1]));
+        assert!(!registry.is_consistent_with(&[1, 3]));
+
+        registry.clear();
+        assert!(registry.is_consistent_with(&[]));
+    }
+
+    #[test]
+    fn failed_creation_keeps_previous_overlays() {
+        let mut registry = OverlayRegistry::default();
+        reconcile(&mut registry, &[1], &[]);
+        let res = registry.reconcile(&[(1, rect_of(1)), (2, rect_of(2))], &[], 0.0, |_, _| {
+            Err("cannot create")
+        });
+        assert_eq!(
+            res,
+            Reconciliation {
+                rematched: vec![],
+                failed: vec![(2, "cannot create")]
+            }
+        );
+        assert_eq!(ids(&registry), vec![1]);
+
+        // 作成できなかったIDは次の呼び出しで作り直す
+        reconcile(&mut registry, &[1, 2], &[]);
+        assert_eq!(ids(&registry), vec![1, 2]);
+    }
+
+    #[test]
+    fn removal_waits_for_leave() {
+        let mut registry = OverlayRegistry::default();
+        reconcile(&mut registry, &[1, 2], &[]);
+        reconcile(&mut registry, &[], &[1]);
+        assert!(registry.is_consistent_with(&[2]));
+        assert!(registry.leaving.contains_key(&1));
+
+        assert!(registry.sweep(50.0).is_empty());
+        assert!(registry.leaving.contains_key(&1));
+        assert_eq!(registry.sweep(100.0), vec![1]);
+        assert!(registry.leaving.is_empty());
+    }
+
+    #[test]
+    fn rematch_cancels_leave() {
+        let mut registry = OverlayRegistry::default();
+        reconcile(&mut registry, &[1], &[]);
+        registry.get_mut(1).unwrap().name = "kept".to_string();
+        reconcile(&mut registry, &[], &[1]);
+
+        // 消えている途中で同じ位置に新しいIDのトラックが追加されると，作り直さずに付け替える
+        let moved = Rect::new(220.0, 20.0, 110.0, 110.0);
+        assert_eq!(
+            reconcile_at(&mut registry, &[(4, moved)], &[]),
+            vec![(1, 4)]
+        );
+        assert_eq!(ids(&registry), vec![4]);
+        assert_eq!(name(&registry, 4), "kept");
+        assert_eq!(registry.get(4).unwrap().leave_started_at, None);
+        assert!(registry.leaving.is_empty());
+
+        assert!(registry.sweep(1000.0).is_empty());
+        assert_eq!(ids(&registry), vec![4]);
+    }
+
+    #[test]
+    fn distant_track_does_not_rematch() {
+        let mut registry = OverlayRegistry::default();
+        reconcile(&mut registry, &[1, 2], &[]);
+        reconcile(&mut registry, &[], &[1, 2]);
+
+        // 離れた位置のトラックは新しく作り，重なる位置のトラックは最も近い画像に付け替える
+        let near_second = Rect::new(380.0, 0.0, 100.0, 100.0);
+        assert_eq!(
+            reconcile_at(&mut registry, &[(5, rect_of(5)), (6, near_second)], &[]),
+            vec![(2, 6)]
+        );
+        assert_eq!(name(&registry, 5), "overlay-5");
+        assert_eq!(name(&registry, 6), "overlay-2");
+        assert!(registry.leaving.contains_key(&1));
+    }
+}
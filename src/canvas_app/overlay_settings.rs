@@ -0,0 +1,153 @@
+use super::dom_utils::{load_image, window};
+use super::laughing_man_image::{OverlayAspect, OverlayLayers};
+use crate::error::AppError;
+
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+
+/// ユーザー画像として受け付けるMIMEタイプ
+const OVERLAY_MIME_TYPES: [&str; 2] = ["image/png", "image/svg+xml"];
+/// ユーザー画像の最大サイズ[byte]．データURLにしてlocalStorageに保存するため小さく抑える
+const MAX_OVERLAY_FILE_SIZE: f64 = 1024.0 * 1024.0;
+
+/// localStorageのキー
+const STORAGE_KEY_IMAGES: &str = "overlay-images";
+const STORAGE_KEY_ANCHOR_X: &str = "overlay-anchor-x";
+const STORAGE_KEY_ANCHOR_Y: &str = "overlay-anchor-y";
+const STORAGE_KEY_ASPECT: &str = "overlay-aspect";
+
+/// localStorageを取得するヘルパー関数
+fn local_storage() -> Result<web_sys::Storage, AppError> {
+    window()
+        .local_storage()?
+        .ok_or(AppError::DomError("Cannot get localStorage.".to_string()))
+}
+
+/// 重ねる画像の設定．localStorageに保存し，再読み込み後も使う
+#[derive(Clone, Debug, PartialEq)]
+pub struct OverlaySettings {
+    /// ユーザー画像のデータURL．空の場合は笑い男画像
+    pub image_data_urls: Vec<String>,
+    /// ユーザー画像の顔の中心に合わせる点(画像の幅・高さに対する比率)
+    pub anchor: (f64, f64),
+    /// ユーザー画像の縦横比の扱い
+    pub aspect: OverlayAspect,
+}
+
+impl Default for OverlaySettings {
+    fn default() -> Self {
+        Self {
+            image_data_urls: Vec::new(),
+            anchor: (0.5, 0.5),
+            aspect: Default::default(),
+        }
+    }
+}
+
+impl OverlaySettings {
+    /// 保存した設定を読み込む．保存されていない項目はデフォルト値とする
+    pub fn load() -> Self {
+        let default = Self::default();
+        let storage = match local_storage() {
+            Ok(storage) => storage,
+            Err(e) => {
+                log::error!("{e}");
+                return default;
+            }
+        };
+        let get_item = |key: &str| storage.get_item(key).ok().flatten();
+        let get_ratio = |key: &str, default: f64| {
+            get_item(key)
+                .and_then(|value| value.parse::<f64>().ok())
+                .unwrap_or(default)
+        };
+
+        Self {
+            image_data_urls: get_item(STORAGE_KEY_IMAGES)
+                .and_then(|value| js_sys::JSON::parse(&value).ok())
+                .and_then(|value| value.dyn_into::<js_sys::Array>().ok())
+                .map(|array| array.iter().filter_map(|value| value.as_string()).collect())
+                .unwrap_or_default(),
+            anchor: (
+                get_ratio(STORAGE_KEY_ANCHOR_X, default.anchor.0),
+                get_ratio(STORAGE_KEY_ANCHOR_Y, default.anchor.1),
+            ),
+            aspect: get_item(STORAGE_KEY_ASPECT)
+                .map(|value| OverlayAspect::from_value(&value))
+                .unwrap_or(default.aspect),
+        }
+    }
+
+    /// 設定を保存する
+    pub fn save(&self) -> Result<(), AppError> {
+        let storage = local_storage()?;
+        if self.image_data_urls.is_empty() {
+            storage.remove_item(STORAGE_KEY_IMAGES)?;
+        } else {
+            let array = self
+                .image_data_urls
+                .iter()
+                .map(|image_data_url| JsValue::from_str(image_data_url))
+                .collect::<js_sys::Array>();
+            storage.set_item(
+                STORAGE_KEY_IMAGES,
+                &String::from(js_sys::JSON::stringify(&array)?),
+            )?;
+        }
+        storage.set_item(STORAGE_KEY_ANCHOR_X, &self.anchor.0.to_string())?;
+        storage.set_item(STORAGE_KEY_ANCHOR_Y, &self.anchor.1.to_string())?;
+        storage.set_item(STORAGE_KEY_ASPECT, self.aspect.to_value())?;
+        Ok(())
+    }
+
+    /// トラックに割り当てる画像の集合．ユーザー画像が無い場合は笑い男画像のみ
+    pub fn layer_set(&self) -> Vec<OverlayLayers> {
+        if self.image_data_urls.is_empty() {
+            return vec![OverlayLayers::default()];
+        }
+        self.image_data_urls
+            .iter()
+            .map(|image_data_url| OverlayLayers {
+                ring_url: None,
+                face_url: image_data_url.clone(),
+                ring_center: self.anchor,
+                anchor: self.anchor,
+                aspect: self.aspect,
+            })
+            .collect()
+    }
+}
+
+/// ユーザーが選択した画像ファイルを検証し，保存できるデータURLにする．
+/// オブジェクトURLで読み込んで画像としてデコードできることとサイズを確認する
+pub async fn load_overlay_file(file: &web_sys::File) -> Result<String, AppError> {
+    let mime_type = file.type_();
+    if !OVERLAY_MIME_TYPES.contains(&mime_type.as_str()) {
+        return Err(AppError::OtherError(format!(
+            "PNGまたはSVGの画像を選択して下さい: {mime_type}"
+        )));
+    }
+    if file.size() > MAX_OVERLAY_FILE_SIZE {
+        return Err(AppError::OtherError(format!(
+            "画像のサイズは{}KB以下にして下さい",
+            MAX_OVERLAY_FILE_SIZE / 1024.0
+        )));
+    }
+
+    // 幅と高さを持たないSVGなどはデコードできても描画できないためここで弾く
+    let object_url = web_sys::Url::create_object_url_with_blob(file)?;
+    let load_res = load_image(&object_url).await;
+    web_sys::Url::revoke_object_url(&object_url)?;
+    load_res?;
+
+    let buffer = JsFuture::from(file.array_buffer()).await?;
+    let binary = js_sys::Uint8Array::new(&buffer)
+        .to_vec()
+        .into_iter()
+        .map(char::from)
+        .collect::<String>();
+    Ok(format!(
+        "data:{mime_type};base64,{}",
+        window().btoa(&binary)?
+    ))
+}
@@ -0,0 +1,200 @@
+use super::geometry::Rect;
+use crate::error::AppError;
+
+use std::collections::VecDeque;
+use wasm_bindgen::JsValue;
+
+/// 保持する検出時間の数(スパークラインの点の数)
+const SPAN_TIME_HISTORY_LEN: usize = 60;
+
+/// 検出のFPSを求める期間[ms]
+const FPS_WINDOW: f64 = 1000.0;
+
+/// パネルの大きさ
+const HUD_WIDTH: f64 = 260.0;
+const HUD_HEIGHT: f64 = 150.0;
+/// スパークラインの高さ
+const SPARKLINE_HEIGHT: f64 = 40.0;
+
+/// 検出の処理時間などの統計
+#[derive(Debug, Default)]
+pub struct PerformanceStats {
+    /// FPS_WINDOWの間にトラッカーを更新した時刻[ms]
+    step_times: VecDeque<f64>,
+    /// 直近の検出時間[ms]．古いものから順に並ぶ
+    span_times: VecDeque<f64>,
+    /// 処理したインターバルの回数
+    tick_count: u32,
+    /// 処理時間がインターバルの間隔を超えた回数
+    over_interval_count: u32,
+}
+
+impl PerformanceStats {
+    /// トラッカーの更新を記録する
+    /// - time: 更新した時刻[ms]
+    /// - span_time: 検出にかかった時間[ms]
+    pub fn record_step(&mut self, time: f64, span_time: f64) {
+        self.step_times.push_back(time);
+        while self
+            .step_times
+            .front()
+            .is_some_and(|&step_time| time - step_time > FPS_WINDOW)
+        {
+            self.step_times.pop_front();
+        }
+
+        self.span_times.push_back(span_time);
+        while self.span_times.len() > SPAN_TIME_HISTORY_LEN {
+            self.span_times.pop_front();
+        }
+    }
+
+    /// インターバル1回分の処理時間[ms]を記録する
+    pub fn record_tick(&mut self, tick_time: f64, interval_span: u32) {
+        self.tick_count += 1;
+        if tick_time > interval_span as f64 {
+            self.over_interval_count += 1;
+        }
+    }
+
+    /// 実際の検出のFPS．FPS_WINDOWの間の更新の回数から求める
+    pub fn fps(&self) -> f64 {
+        match (self.step_times.front(), self.step_times.back()) {
+            (Some(first), Some(last)) if last > first => {
+                (self.step_times.len() - 1) as f64 * 1000.0 / (last - first)
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// 直近の検出時間[ms]
+    pub fn span_times(&self) -> &VecDeque<f64> {
+        &self.span_times
+    }
+
+    /// 処理時間がインターバルの間隔を超えた回数と，処理したインターバルの回数
+    pub fn over_interval_count(&self) -> (u32, u32) {
+        (self.over_interval_count, self.tick_count)
+    }
+}
+
+/// ビデオの再生品質(getVideoPlaybackQuality)から取得した，落としたフレームの数と全フレームの数
+pub fn dropped_frames(video: &web_sys::HtmlVideoElement) -> (u32, u32) {
+    let quality = video.get_video_playback_quality();
+    (quality.dropped_video_frames(), quality.total_video_frames())
+}
+
+/// 統計をパネルとして(x, y)に描画する
+/// - interval_span: インターバルの間隔[ms]．スパークラインに基準線として描く
+pub fn draw_performance_hud(
+    context: &web_sys::CanvasRenderingContext2d,
+    x: f64,
+    y: f64,
+    stats: &PerformanceStats,
+    interval_span: u32,
+    (dropped_frames, total_frames): (u32, u32),
+) -> Result<(), AppError> {
+    context.save();
+    let draw_res = draw_panel(
+        context,
+        &Rect::new(x, y, HUD_WIDTH, HUD_HEIGHT),
+        stats,
+        interval_span,
+        (dropped_frames, total_frames),
+    );
+    context.restore();
+    draw_res
+}
+
+fn draw_panel(
+    context: &web_sys::CanvasRenderingContext2d,
+    panel: &Rect,
+    stats: &PerformanceStats,
+    interval_span: u32,
+    (dropped_frames, total_frames): (u32, u32),
+) -> Result<(), AppError> {
+    context.set_fill_style(&JsValue::from_str("rgba(0, 0, 0, 0.6)"));
+    context.fill_rect(panel.x, panel.y, panel.width, panel.height);
+
+    let (over_interval_count, tick_count) = stats.over_interval_count();
+    let last_span_time = stats.span_times().back().copied().unwrap_or(0.0);
+    context.set_font("14px monospace");
+    context.set_fill_style(&JsValue::from_str("white"));
+    for (i, line) in [
+        format!("detection: {:.1} fps", stats.fps()),
+        format!("span time: {last_span_time:.1} [ms]"),
+        format!("over interval: {over_interval_count} / {tick_count} ticks"),
+        format!("dropped frames: {dropped_frames} / {total_frames}"),
+    ]
+    .iter()
+    .enumerate()
+    {
+        context.fill_text(line, panel.x + 8.0, panel.y + 18.0 + i as f64 * 18.0)?;
+    }
+
+    // 検出時間のスパークライン．インターバルの間隔か最大値の大きい方を上端とする
+    let sparkline = Rect::new(
+        panel.x + 8.0,
+        panel.bottom() - SPARKLINE_HEIGHT - 8.0,
+        panel.width - 16.0,
+        SPARKLINE_HEIGHT,
+    );
+    let span_times = stats.span_times();
+    let max_span_time = span_times
+        .iter()
+        .copied()
+        .fold(interval_span as f64, f64::max)
+        .max(1.0);
+    let to_y = |span_time: f64| sparkline.bottom() - span_time / max_span_time * sparkline.height;
+
+    context.set_stroke_style(&JsValue::from_str("rgba(255, 255, 255, 0.5)"));
+    context.begin_path();
+    context.move_to(sparkline.x, to_y(interval_span as f64));
+    context.line_to(sparkline.right(), to_y(interval_span as f64));
+    context.stroke();
+
+    context.set_stroke_style(&JsValue::from_str("#7CFC00"));
+    context.begin_path();
+    let step_x = sparkline.width / (SPAN_TIME_HISTORY_LEN - 1) as f64;
+    for (i, &span_time) in span_times.iter().enumerate() {
+        let x = sparkline.x + i as f64 * step_x;
+        if i == 0 {
+            context.move_to(x, to_y(span_time));
+        } else {
+            context.line_to(x, to_y(span_time));
+        }
+    }
+    context.stroke();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fps_and_over_interval_ticks() {
+        let mut stats = PerformanceStats::default();
+        assert_eq!(stats.fps(), 0.0);
+
+        // 100[ms]ごとに更新すると10fps．FPS_WINDOWより古い更新は数えない
+        for i in 0..30 {
+            stats.record_step(i as f64 * 100.0, 20.0);
+        }
+        assert!((stats.fps() - 10.0).abs() < 1e-9);
+
+        stats.record_tick(50.0, 100);
+        stats.record_tick(120.0, 100);
+        assert_eq!(stats.over_interval_count(), (1, 2));
+    }
+
+    #[test]
+    fn span_times_are_bounded() {
+        let mut stats = PerformanceStats::default();
+        for i in 0..(SPAN_TIME_HISTORY_LEN + 5) {
+            stats.record_step(i as f64, i as f64);
+        }
+        assert_eq!(stats.span_times().len(), SPAN_TIME_HISTORY_LEN);
+        assert_eq!(stats.span_times().front(), Some(&5.0));
+    }
+}
@@ -0,0 +1,69 @@
+use leptos::*;
+use std::ops::Deref;
+use wasm_bindgen_futures::JsFuture;
+
+/// ビデオを取得するヘルパー関数
+fn video_element(video_node_ref: NodeRef<leptos::html::Video>) -> web_sys::HtmlVideoElement {
+    video_node_ref
+        .get()
+        .expect("Cannot Get Video Element")
+        .deref()
+        .clone()
+}
+
+/// 動画ファイルの再生・一時停止・シーク・ループを操作するコンポーネント
+#[component]
+pub fn PlaybackControls(
+    cx: Scope,
+    video_node_ref: NodeRef<leptos::html::Video>,
+    is_paused: ReadSignal<bool>,
+    current_time: ReadSignal<f64>,
+    duration: ReadSignal<f64>,
+) -> impl IntoView {
+    let toggle_play = move |_| {
+        let video = video_element(video_node_ref);
+        if video.paused() {
+            match video.play() {
+                Ok(play_promise) => spawn_local(async move {
+                    if let Err(e) = JsFuture::from(play_promise).await {
+                        log::error!("{e:?}");
+                    }
+                }),
+                Err(e) => log::error!("{e:?}"),
+            }
+        } else if let Err(e) = video.pause() {
+            log::error!("{e:?}");
+        }
+    };
+
+    // シークした場合はトラッカー側で再生位置の変化を検出して初期化する
+    let seek = move |ev| {
+        if let Ok(time) = event_target_value(&ev).parse::<f64>() {
+            video_element(video_node_ref).set_current_time(time);
+        }
+    };
+
+    let toggle_loop = move |ev| {
+        video_element(video_node_ref).set_loop(event_target_checked(&ev));
+    };
+
+    view! {cx,
+        <div class="playback-controls">
+            <button on:click=toggle_play>
+                {move || if is_paused.get() {"再生"} else {"一時停止"}}
+            </button>
+            <input type="range" class="seek-bar" min=0 step=0.01
+                prop:max=move || duration.get()
+                prop:value=move || current_time.get()
+                on:input=seek
+            />
+            <span class="playback-time">
+                {move || format!("{:.1} / {:.1} [s]", current_time.get(), duration.get())}
+            </span>
+            <label>
+                "ループ:"
+                <input type="checkbox" on:change=toggle_loop/>
+            </label>
+        </div>
+    }
+}
@@ -0,0 +1,392 @@
+use super::dom_utils::canvas;
+use super::geometry::{Rect, Transform};
+use crate::error::AppError;
+
+use wasm_bindgen::{Clamped, JsValue};
+
+/// 顔の匿名化の方法
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum RedactionStyle {
+    /// 匿名化せずに枠と特徴点を表示する(確認用)
+    Outline,
+    /// 笑い男画像を重ねる
+    #[default]
+    LaughingMan,
+    /// ぼかし
+    Blur,
+    /// モザイク
+    Pixelate,
+    /// 塗りつぶし
+    SolidBox,
+    /// 目線
+    EyeBar,
+}
+
+impl RedactionStyle {
+    /// セレクトボックスの値に変換
+    pub fn to_value(self) -> &'static str {
+        match self {
+            RedactionStyle::Outline => "outline",
+            RedactionStyle::LaughingMan => "laughing-man",
+            RedactionStyle::Blur => "blur",
+            RedactionStyle::Pixelate => "pixelate",
+            RedactionStyle::SolidBox => "solid-box",
+            RedactionStyle::EyeBar => "eye-bar",
+        }
+    }
+
+    /// セレクトボックスの値から変換
+    pub fn from_value(value: &str) -> Self {
+        match value {
+            "outline" => RedactionStyle::Outline,
+            "blur" => RedactionStyle::Blur,
+            "pixelate" => RedactionStyle::Pixelate,
+            "solid-box" => RedactionStyle::SolidBox,
+            "eye-bar" => RedactionStyle::EyeBar,
+            _ => RedactionStyle::LaughingMan,
+        }
+    }
+
+    /// キャンバスに描画した元の映像の画素を加工するかどうか
+    pub fn needs_source_pixels(self) -> bool {
+        matches!(self, RedactionStyle::Blur | RedactionStyle::Pixelate)
+    }
+}
+
+/// 匿名化のオプション．強さは方法ごとに持つ
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RedactionOptions {
+    pub style: RedactionStyle,
+    /// ぼかしの半径(顔の幅に対する比率)
+    pub blur_radius_ratio: f64,
+    /// モザイクのブロックの大きさ(顔の幅に対する比率)
+    pub pixelate_block_ratio: f64,
+    /// 塗りつぶしの不透明度[0.0, 1.0]
+    pub solid_opacity: f64,
+    /// 目線の太さ(顔の高さに対する比率)
+    pub eye_bar_thickness_ratio: f64,
+}
+
+impl Default for RedactionOptions {
+    fn default() -> Self {
+        Self {
+            style: Default::default(),
+            blur_radius_ratio: 0.08,
+            pixelate_block_ratio: 0.1,
+            solid_opacity: 1.0,
+            eye_bar_thickness_ratio: 0.18,
+        }
+    }
+}
+
+impl RedactionOptions {
+    /// 指定した方法で匿名化できない場合(画像を読み込めない場合など)に代わりに使う，不透明な塗りつぶし
+    pub fn opaque_solid_box(&self) -> Self {
+        Self {
+            style: RedactionStyle::SolidBox,
+            solid_opacity: 1.0,
+            ..*self
+        }
+    }
+}
+
+/// 元の映像をrectsの範囲に限って描画する．ぼかしとモザイクはこの画素を加工する
+/// - source_to_canvas: 映像の座標から表示の座標への変換
+pub fn draw_video_in_rects(
+    context: &web_sys::CanvasRenderingContext2d,
+    video: &web_sys::HtmlVideoElement,
+    source_to_canvas: &Transform,
+    rects: &[Rect],
+) -> Result<(), AppError> {
+    context.save();
+    context.begin_path();
+    for rect in rects {
+        context.rect(rect.x, rect.y, rect.width, rect.height);
+    }
+    context.clip();
+    let draw_res = source_to_canvas
+        .apply_to_context(context)
+        .and_then(|_| context.draw_image_with_html_video_element(video, 0.0, 0.0));
+    context.restore();
+    Ok(draw_res?)
+}
+
+/// 顔の矩形(表示の座標)を匿名化する．枠と笑い男画像の場合は何もしない
+/// - eyes: 左右の目の位置(表示の座標)．Noneの場合は顔の矩形から推定する
+pub fn redact(
+    context: &web_sys::CanvasRenderingContext2d,
+    rect: &Rect,
+    eyes: Option<((f64, f64), (f64, f64))>,
+    options: &RedactionOptions,
+) -> Result<(), AppError> {
+    match options.style {
+        RedactionStyle::Outline | RedactionStyle::LaughingMan => Ok(()),
+        RedactionStyle::Blur => {
+            let radius = (rect.width * options.blur_radius_ratio).round().max(1.0) as usize;
+            process_pixels(context, rect, |data, width, height| {
+                // 3回の箱型ぼかしでガウスぼかしを近似する
+                for _ in 0..3 {
+                    box_blur(data, width, height, radius);
+                }
+            })
+        }
+        RedactionStyle::Pixelate => {
+            let block_size = (rect.width * options.pixelate_block_ratio).round().max(1.0) as usize;
+            process_pixels(context, rect, |data, width, height| {
+                pixelate(data, width, height, block_size)
+            })
+        }
+        RedactionStyle::SolidBox => {
+            context.save();
+            context.set_global_alpha(options.solid_opacity.clamp(0.0, 1.0));
+            context.set_fill_style(&JsValue::from_str("black"));
+            context.fill_rect(rect.x, rect.y, rect.width, rect.height);
+            context.restore();
+            Ok(())
+        }
+        RedactionStyle::EyeBar => draw_eye_bar(context, rect, eyes, options),
+    }
+}
+
+/// 目を結ぶ線に沿って黒い帯を描画する
+fn draw_eye_bar(
+    context: &web_sys::CanvasRenderingContext2d,
+    rect: &Rect,
+    eyes: Option<((f64, f64), (f64, f64))>,
+    options: &RedactionOptions,
+) -> Result<(), AppError> {
+    // 特徴点が無い場合は一般的な顔の目の位置とする
+    let ((left_x, left_y), (right_x, right_y)) = eyes.unwrap_or((
+        (rect.x + rect.width * 0.3, rect.y + rect.height * 0.4),
+        (rect.x + rect.width * 0.7, rect.y + rect.height * 0.4),
+    ));
+    let (dx, dy) = (right_x - left_x, right_y - left_y);
+    let length = (dx.hypot(dy) * 2.2).max(rect.width * 0.8);
+    let thickness = rect.height * options.eye_bar_thickness_ratio;
+
+    context.save();
+    context.set_fill_style(&JsValue::from_str("black"));
+    let draw_res = context
+        .translate((left_x + right_x) / 2.0, (left_y + right_y) / 2.0)
+        .and_then(|_| context.rotate(dy.atan2(dx)));
+    if draw_res.is_ok() {
+        context.fill_rect(-length / 2.0, -thickness / 2.0, length, thickness);
+    }
+    context.restore();
+    Ok(draw_res?)
+}
+
+/// キャンバスの矩形の範囲(キャンバス内に限る)の画素をRGBAのまま加工する
+fn process_pixels<F>(
+    context: &web_sys::CanvasRenderingContext2d,
+    rect: &Rect,
+    process: F,
+) -> Result<(), AppError>
+where
+    F: FnOnce(&mut [u8], usize, usize),
+{
+    let canvas = canvas(context)?;
+    let bounds = Rect::new(0.0, 0.0, canvas.width() as f64, canvas.height() as f64);
+    let Some(clamped) = rect.clamp_to(&bounds) else {
+        return Ok(());
+    };
+    let (x, y) = (clamped.x.floor(), clamped.y.floor());
+    let width = (clamped.right().ceil() - x) as usize;
+    let height = (clamped.bottom().ceil() - y) as usize;
+    if width == 0 || height == 0 {
+        return Ok(());
+    }
+
+    let mut data = context
+        .get_image_data(x, y, width as f64, height as f64)?
+        .data()
+        .0;
+    process(&mut data, width, height);
+
+    let image_data = web_sys::ImageData::new_with_u8_clamped_array_and_sh(
+        Clamped(&data),
+        width as u32,
+        height as u32,
+    )?;
+    context.put_image_data(&image_data, x, y)?;
+    Ok(())
+}
+
+/// 半径radiusの箱型ぼかし(水平・垂直)．端は端の画素を延長する．半径0の場合は何もしない
+fn box_blur(data: &mut [u8], width: usize, height: usize, radius: usize) {
+    if radius == 0 || width == 0 || height == 0 {
+        return;
+    }
+    let mut line = Vec::new();
+    // 水平方向
+    for y in 0..height {
+        line.clear();
+        line.extend((0..width).map(|x| pixel(data, (y * width + x) * 4)));
+        for (x, value) in blur_line(&line, radius).into_iter().enumerate() {
+            set_pixel(data, (y * width + x) * 4, value);
+        }
+    }
+    // 垂直方向
+    for x in 0..width {
+        line.clear();
+        line.extend((0..height).map(|y| pixel(data, (y * width + x) * 4)));
+        for (y, value) in blur_line(&line, radius).into_iter().enumerate() {
+            set_pixel(data, (y * width + x) * 4, value);
+        }
+    }
+}
+
+/// 一列の画素の移動平均．累積和を使うため半径によらず線形時間
+fn blur_line(line: &[[u32; 4]], radius: usize) -> Vec<[u32; 4]> {
+    let len = line.len();
+    let mut prefix = vec![[0_u32; 4]; len + 1];
+    for (i, value) in line.iter().enumerate() {
+        prefix[i + 1] = std::array::from_fn(|c| prefix[i][c] + value[c]);
+    }
+
+    (0..len)
+        .map(|i| {
+            let start = i.saturating_sub(radius);
+            let end = (i + radius + 1).min(len);
+            // 範囲外の画素は端の画素とみなす
+            let (head, tail) = (
+                radius.saturating_sub(i),
+                (i + radius + 1).saturating_sub(len),
+            );
+            let count = (2 * radius + 1) as u32;
+            std::array::from_fn(|c| {
+                (prefix[end][c] - prefix[start][c]
+                    + line[0][c] * head as u32
+                    + line[len - 1][c] * tail as u32)
+                    / count
+            })
+        })
+        .collect()
+}
+
+/// block_size四方のブロックごとに平均色で塗りつぶす．端のブロックは残りの画素だけで平均する．
+/// block_sizeが1以下の場合は何もしない
+fn pixelate(data: &mut [u8], width: usize, height: usize, block_size: usize) {
+    if block_size <= 1 {
+        return;
+    }
+    for block_y in (0..height).step_by(block_size) {
+        for block_x in (0..width).step_by(block_size) {
+            let ys = block_y..(block_y + block_size).min(height);
+            let xs = block_x..(block_x + block_size).min(width);
+
+            let mut sum = [0_u32; 4];
+            for y in ys.clone() {
+                for x in xs.clone() {
+                    let value = pixel(data, (y * width + x) * 4);
+                    sum = std::array::from_fn(|c| sum[c] + value[c]);
+                }
+            }
+            let count = (ys.len() * xs.len()) as u32;
+            let average = sum.map(|value| value / count);
+
+            for y in ys.clone() {
+                for x in xs.clone() {
+                    set_pixel(data, (y * width + x) * 4, average);
+                }
+            }
+        }
+    }
+}
+
+/// offsetの位置のRGBA
+fn pixel(data: &[u8], offset: usize) -> [u32; 4] {
+    std::array::from_fn(|c| data[offset + c] as u32)
+}
+
+/// offsetの位置にRGBAを設定する
+fn set_pixel(data: &mut [u8], offset: usize, value: [u32; 4]) {
+    for (dst, value) in data[offset..offset + 4].iter_mut().zip(value) {
+        *dst = value as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 全ての画素が同じRGBAの画像
+    fn uniform_image(width: usize, height: usize, value: [u8; 4]) -> Vec<u8> {
+        value.repeat(width * height)
+    }
+
+    /// 赤の値を並べた画像(不透明)
+    fn red_image(reds: &[u8]) -> Vec<u8> {
+        reds.iter().flat_map(|&red| [red, 0, 0, 255]).collect()
+    }
+
+    fn reds(data: &[u8]) -> Vec<u8> {
+        data.chunks(4).map(|value| value[0]).collect()
+    }
+
+    #[test]
+    fn uniform_image_is_unchanged_by_blur() {
+        let mut data = uniform_image(6, 5, [10, 20, 30, 255]);
+        let expected = data.clone();
+        box_blur(&mut data, 6, 5, 2);
+        assert_eq!(data, expected);
+    }
+
+    #[test]
+    fn bright_pixel_spreads_and_keeps_intensity() {
+        // 5x5の中央だけが明るい
+        let mut reds_in = vec![0; 25];
+        reds_in[12] = 225;
+        let mut data = red_image(&reds_in);
+        box_blur(&mut data, 5, 5, 1);
+
+        let reds_out = reds(&data);
+        assert_eq!(reds_out.iter().map(|&red| red as u32).sum::<u32>(), 225);
+        for y in 0..5 {
+            for x in 0..5 {
+                let is_neighbor = (1..=3).contains(&x) && (1..=3).contains(&y);
+                assert_eq!(reds_out[y * 5 + x], if is_neighbor { 25 } else { 0 });
+            }
+        }
+    }
+
+    #[test]
+    fn blur_line_extends_edges() {
+        let line = [[90, 0, 0, 255], [0, 0, 0, 255], [0, 0, 0, 255]];
+        let blurred = blur_line(&line, 1);
+        // 左端の外側は左端の画素とみなす
+        assert_eq!(blurred[0], [60, 0, 0, 255]);
+        assert_eq!(blurred[1], [30, 0, 0, 255]);
+        assert_eq!(blurred[2], [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn pixelate_fills_blocks_with_average() {
+        // 5x1の画像を2画素のブロックにすると，右端は1画素だけのブロックになる
+        let mut data = red_image(&[10, 30, 50, 70, 90]);
+        pixelate(&mut data, 5, 1, 2);
+        assert_eq!(reds(&data), vec![20, 20, 60, 60, 90]);
+
+        // 3x3の画像を2画素のブロックにする
+        let mut data = red_image(&[0, 4, 8, 8, 12, 16, 30, 60, 90]);
+        pixelate(&mut data, 3, 3, 2);
+        assert_eq!(reds(&data), vec![6, 6, 12, 6, 6, 12, 45, 45, 90]);
+    }
+
+    #[test]
+    fn small_radius_and_block_are_identity() {
+        let original = red_image(&[0, 50, 100, 150, 200, 250]);
+        for size in [0, 1] {
+            let mut data = original.clone();
+            pixelate(&mut data, 3, 2, size);
+            assert_eq!(data, original);
+        }
+        let mut data = original.clone();
+        box_blur(&mut data, 3, 2, 0);
+        assert_eq!(data, original);
+
+        // 空の画像でも範囲外にアクセスしない
+        box_blur(&mut [], 0, 0, 3);
+        pixelate(&mut [], 0, 0, 3);
+    }
+}
@@ -0,0 +1,94 @@
+use super::geometry::Rect;
+
+use std::collections::HashMap;
+
+/// 外挿する最大の時間(直前の更新間隔に対する比率)．検出が途切れた場合に画像が飛んでいかないようにする
+const MAX_EXTRAPOLATION_RATIO: f64 = 1.0;
+
+/// トラッカーが更新した時刻[ms]とroiの矩形
+#[derive(Clone, Copy, Debug)]
+struct Keyframe {
+    time: f64,
+    rect: Rect,
+}
+
+/// トラッカーの更新の間のroiの位置を求める．
+/// 過去の更新の間を補間すると表示が遅れて顔が見えてしまうため，直近の2回の更新から等速で外挿する
+#[derive(Debug, Default)]
+pub struct RoiMotion {
+    /// トラックIDごとの前回と今回の更新
+    tracks: HashMap<u32, (Option<Keyframe>, Keyframe)>,
+}
+
+impl RoiMotion {
+    /// トラッカーの更新を記録する．rectsに含まれないトラックは削除する．
+    /// added_idsのトラックは新しいトラックとして履歴を持たない
+    pub fn update(&mut self, time: f64, rects: &[(u32, Rect)], added_ids: &[u32]) {
+        let mut tracks = HashMap::with_capacity(rects.len());
+        for &(id, rect) in rects {
+            let previous = if added_ids.contains(&id) {
+                None
+            } else {
+                self.tracks.get(&id).map(|(_, current)| *current)
+            };
+            tracks.insert(id, (previous, Keyframe { time, rect }));
+        }
+        self.tracks = tracks;
+    }
+
+    /// 時刻time[ms]におけるroiの矩形．記録の無いトラックはNone
+    pub fn sample(&self, id: u32, time: f64) -> Option<Rect> {
+        let (previous, current) = self.tracks.get(&id)?;
+        let Some(previous) = previous else {
+            return Some(current.rect);
+        };
+        let span = current.time - previous.time;
+        if span <= 0.0 {
+            return Some(current.rect);
+        }
+        let ratio = ((time - current.time) / span).clamp(0.0, MAX_EXTRAPOLATION_RATIO);
+        Some(previous.rect.lerp(&current.rect, 1.0 + ratio))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extrapolates_and_clamps() {
+        let mut motion = RoiMotion::default();
+        motion.update(0.0, &[(1, Rect::new(0.0, 0.0, 10.0, 10.0))], &[1]);
+        motion.update(100.0, &[(1, Rect::new(10.0, 0.0, 12.0, 10.0))], &[]);
+
+        assert_eq!(
+            motion.sample(1, 50.0),
+            Some(Rect::new(10.0, 0.0, 12.0, 10.0))
+        );
+        assert_eq!(
+            motion.sample(1, 150.0),
+            Some(Rect::new(15.0, 0.0, 13.0, 10.0))
+        );
+        // 1回分の更新間隔より先には進まない
+        assert_eq!(
+            motion.sample(1, 500.0),
+            Some(Rect::new(20.0, 0.0, 14.0, 10.0))
+        );
+        assert_eq!(motion.sample(2, 150.0), None);
+    }
+
+    #[test]
+    fn added_track_has_no_history() {
+        let mut motion = RoiMotion::default();
+        motion.update(0.0, &[(0, Rect::new(0.0, 0.0, 10.0, 10.0))], &[0]);
+        // トラッカーを作り直して同じIDが別の位置に現れる
+        motion.update(100.0, &[(0, Rect::new(200.0, 0.0, 10.0, 10.0))], &[0]);
+        assert_eq!(
+            motion.sample(0, 150.0),
+            Some(Rect::new(200.0, 0.0, 10.0, 10.0))
+        );
+
+        motion.update(200.0, &[], &[]);
+        assert_eq!(motion.sample(0, 250.0), None);
+    }
+}
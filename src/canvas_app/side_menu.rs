@@ -1,4 +1,10 @@
-use crate::canvas_app::{CanvasAppOptions, LaughingManOptions, TrackerOptions};
+use crate::canvas_app::{
+    load_overlay_file, CameraDevice, CameraSelection, CanvasAppOptions, ConstraintRange,
+    DebugViewOptions, FailSafeMode, FailSafeOptions, LaughingManOptions, ObjectFit, OverlayAspect,
+    OverlayAssignment, OverlayFit, OverlayRenderer, OverlaySettings, OverlayTransition,
+    RedactionOptions, RedactionStyle, RoiGrowthMode, RoiGrowthOptions, TrackerOptions,
+    VideoOptions,
+};
 use leptos::*;
 
 use std::fmt::Debug;
@@ -17,6 +23,20 @@ where
         .expect("Cannot value Convert")
 }
 
+/// 空の場合はNoneとする
+fn parse_optional_input_value<T, E>(node_ref: NodeRef<leptos::html::Input>) -> Option<T>
+where
+    T: FromStr<Err = E>,
+    E: Debug,
+{
+    let value = node_ref.get().expect("Cannot Get Input Element").value();
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.parse().expect("Cannot value Convert"))
+    }
+}
+
 fn get_input_checked(node_ref: NodeRef<leptos::html::Input>) -> bool {
     node_ref.get().expect("Cannot get Input Element").checked()
 }
@@ -28,6 +48,95 @@ fn set_input_value_as_number(node_ref: NodeRef<leptos::html::Input>, value: f64)
         .set_value_as_number(value)
 }
 
+fn set_optional_input_value(node_ref: NodeRef<leptos::html::Input>, value: Option<f64>) {
+    let input = node_ref.get().expect("Cannot Get Input Element");
+    match value {
+        Some(value) => input.set_value_as_number(value),
+        None => input.set_value(""),
+    }
+}
+
+fn optional_to_string(value: Option<f64>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+/// 最小・理想・最大の三つの入力要素
+#[derive(Clone, Copy)]
+struct ConstraintRangeNodeRefs {
+    min: NodeRef<leptos::html::Input>,
+    ideal: NodeRef<leptos::html::Input>,
+    max: NodeRef<leptos::html::Input>,
+}
+
+impl ConstraintRangeNodeRefs {
+    fn new(cx: Scope) -> Self {
+        Self {
+            min: create_node_ref(cx),
+            ideal: create_node_ref(cx),
+            max: create_node_ref(cx),
+        }
+    }
+
+    fn parse(&self) -> ConstraintRange {
+        ConstraintRange {
+            min: parse_optional_input_value(self.min),
+            ideal: parse_optional_input_value(self.ideal),
+            max: parse_optional_input_value(self.max),
+        }
+    }
+
+    fn set(&self, range: ConstraintRange) {
+        set_optional_input_value(self.min, range.min);
+        set_optional_input_value(self.ideal, range.ideal);
+        set_optional_input_value(self.max, range.max);
+    }
+}
+
+/// 制約の範囲の入力
+#[component]
+fn ConstraintRangeInput(
+    cx: Scope,
+    label: &'static str,
+    step: f64,
+    initial: ConstraintRange,
+    node_refs: ConstraintRangeNodeRefs,
+) -> impl IntoView {
+    let ConstraintRangeNodeRefs {
+        min: min_nr,
+        ideal: ideal_nr,
+        max: max_nr,
+    } = node_refs;
+
+    view! {cx,
+        <label>
+            {label}
+            <input type="number" min=0 step=step placeholder="最小"
+                value={optional_to_string(initial.min)}
+                node_ref=min_nr
+            />
+            <input type="number" min=0 step=step placeholder="理想"
+                value={optional_to_string(initial.ideal)}
+                node_ref=ideal_nr
+            />
+            <input type="number" min=0 step=step placeholder="最大"
+                value={optional_to_string(initial.max)}
+                node_ref=max_nr
+            />
+        </label>
+    }
+}
+
+fn get_select_value(node_ref: NodeRef<leptos::html::Select>) -> String {
+    node_ref.get().expect("Cannot Get Select Element").value()
+}
+
+fn set_select_value(node_ref: NodeRef<leptos::html::Select>, value: &str) {
+    node_ref
+        .get()
+        .expect("Cannot Get Select Element")
+        .set_value(value)
+}
+
 fn set_input_checked(node_ref: NodeRef<leptos::html::Input>, checked: bool) {
     node_ref
         .get()
@@ -36,32 +145,104 @@ fn set_input_checked(node_ref: NodeRef<leptos::html::Input>, checked: bool) {
 }
 
 #[component]
-pub fn SideMenu<F>(cx: Scope, canvas_app_options: CanvasAppOptions, on_apply: F) -> impl IntoView
+pub fn SideMenu<F, G, H>(
+    cx: Scope,
+    canvas_app_options: CanvasAppOptions,
+    cameras: ReadSignal<Vec<CameraDevice>>,
+    on_apply: F,
+    on_select_camera: G,
+    on_export_trajectories: H,
+    restored_video_options: ReadSignal<Option<VideoOptions>>,
+) -> impl IntoView
 where
     F: Fn(CanvasAppOptions) + 'static,
+    G: Fn(CameraSelection) + 'static,
+    H: Fn() + 'static,
 {
     //　初期値
     let CanvasAppOptions {
+        video_options,
         tracker_options,
         laughing_man_options,
-        is_active_laughing_man,
+        redaction_options,
+        is_mirrored,
+        object_fit,
         interval_span,
+        overlay_settings,
+        fail_safe_options,
+        debug_view_options,
+        show_performance_hud,
     } = canvas_app_options;
 
     // node_ref
+    let video_width_nrs = ConstraintRangeNodeRefs::new(cx);
+    let video_height_nrs = ConstraintRangeNodeRefs::new(cx);
+    let video_frame_rate_nrs = ConstraintRangeNodeRefs::new(cx);
+
+    // ストリームに適用できなかった場合は以前のオプションに戻す
+    create_effect(cx, move |_| {
+        if let Some(video_options) = restored_video_options.get() {
+            video_width_nrs.set(video_options.width);
+            video_height_nrs.set(video_options.height);
+            video_frame_rate_nrs.set(video_options.frame_rate);
+        }
+    });
     let image_over_video_scale_nr = create_node_ref::<leptos::html::Input>(cx);
     let min_face_size_nr = create_node_ref::<leptos::html::Input>(cx);
     let score_thresh_nr = create_node_ref::<leptos::html::Input>(cx);
     let pyramid_scale_factor_nr = create_node_ref::<leptos::html::Input>(cx);
     let slide_window_step_nr = create_node_ref::<leptos::html::Input>(cx);
-    let is_active_laughing_man_nr = create_node_ref::<leptos::html::Input>(cx);
+    let redaction_style_nr = create_node_ref::<leptos::html::Select>(cx);
+    let blur_radius_ratio_nr = create_node_ref::<leptos::html::Input>(cx);
+    let pixelate_block_ratio_nr = create_node_ref::<leptos::html::Input>(cx);
+    let solid_opacity_nr = create_node_ref::<leptos::html::Input>(cx);
+    let eye_bar_thickness_ratio_nr = create_node_ref::<leptos::html::Input>(cx);
+    let is_mirrored_nr = create_node_ref::<leptos::html::Input>(cx);
+    let object_fit_nr = create_node_ref::<leptos::html::Select>(cx);
     let allowable_not_detect_count_nr = create_node_ref::<leptos::html::Input>(cx);
+    let roi_growth_mode_nr = create_node_ref::<leptos::html::Select>(cx);
+    let roi_growth_rate_nr = create_node_ref::<leptos::html::Input>(cx);
+    let roi_growth_max_ratio_nr = create_node_ref::<leptos::html::Input>(cx);
     let laughing_man_size_ratio_nr = create_node_ref::<leptos::html::Input>(cx);
-    let laughing_man_shift_ratio_nr = create_node_ref::<leptos::html::Input>(cx);
+    let laughing_man_shift_x_ratio_nr = create_node_ref::<leptos::html::Input>(cx);
+    let laughing_man_shift_y_ratio_nr = create_node_ref::<leptos::html::Input>(cx);
+    let overlay_fit_nr = create_node_ref::<leptos::html::Select>(cx);
+    let laughing_man_z_index_nr = create_node_ref::<leptos::html::Input>(cx);
+    let overlay_renderer_nr = create_node_ref::<leptos::html::Select>(cx);
+    let ring_rotation_speed_nr = create_node_ref::<leptos::html::Input>(cx);
     let interval_span_nr = create_node_ref::<leptos::html::Input>(cx);
+    let overlay_anchor_x_nr = create_node_ref::<leptos::html::Input>(cx);
+    let overlay_anchor_y_nr = create_node_ref::<leptos::html::Input>(cx);
+    let overlay_aspect_nr = create_node_ref::<leptos::html::Select>(cx);
+    let overlay_assignment_nr = create_node_ref::<leptos::html::Select>(cx);
+    let smooth_motion_nr = create_node_ref::<leptos::html::Input>(cx);
+    let appear_transition_nr = create_node_ref::<leptos::html::Select>(cx);
+    let disappear_transition_nr = create_node_ref::<leptos::html::Select>(cx);
+    let transition_duration_nr = create_node_ref::<leptos::html::Input>(cx);
+    let fail_safe_mode_nr = create_node_ref::<leptos::html::Select>(cx);
+    let span_time_budget_nr = create_node_ref::<leptos::html::Input>(cx);
+    let center_region_ratio_nr = create_node_ref::<leptos::html::Input>(cx);
+    let recovery_count_nr = create_node_ref::<leptos::html::Input>(cx);
+    let debug_raw_detections_nr = create_node_ref::<leptos::html::Input>(cx);
+    let debug_tracked_boxes_nr = create_node_ref::<leptos::html::Input>(cx);
+    let debug_labels_nr = create_node_ref::<leptos::html::Input>(cx);
+    let debug_landmarks_nr = create_node_ref::<leptos::html::Input>(cx);
+    let debug_trails_nr = create_node_ref::<leptos::html::Input>(cx);
+    let trail_length_nr = create_node_ref::<leptos::html::Input>(cx);
+    let show_performance_hud_nr = create_node_ref::<leptos::html::Input>(cx);
+
+    // 検証済みのユーザー画像(データURL)の集合と検証結果のメッセージ
+    let overlay_images = create_rw_signal(cx, overlay_settings.image_data_urls.clone());
+    let (overlay_message, set_overlay_message) = create_signal(cx, Option::<String>::None);
 
     // apply, default関数
     let apply = move |_| {
+        let video_options = VideoOptions {
+            width: video_width_nrs.parse(),
+            height: video_height_nrs.parse(),
+            frame_rate: video_frame_rate_nrs.parse(),
+        };
+
         let tracker_options = TrackerOptions {
             image_over_video_scale: parse_input_value(image_over_video_scale_nr),
             min_face_size: parse_input_value(min_face_size_nr),
@@ -69,33 +250,101 @@ where
             pyramid_scale_factor: parse_input_value(pyramid_scale_factor_nr),
             slide_window_step: parse_input_value(slide_window_step_nr),
             allowable_not_detect_count: parse_input_value(allowable_not_detect_count_nr),
+            roi_growth: RoiGrowthOptions {
+                mode: RoiGrowthMode::from_value(&get_select_value(roi_growth_mode_nr)),
+                rate: parse_input_value(roi_growth_rate_nr),
+                max_ratio: parse_input_value(roi_growth_max_ratio_nr),
+            },
+            trail_length: parse_input_value(trail_length_nr),
         };
 
         let laughing_man_options = LaughingManOptions {
             laughing_man_size_ratio: parse_input_value(laughing_man_size_ratio_nr),
-            laughing_man_shift_ratio: parse_input_value(laughing_man_shift_ratio_nr),
-            ..Default::default()
+            laughing_man_shift_x_ratio: parse_input_value(laughing_man_shift_x_ratio_nr),
+            laughing_man_shift_y_ratio: parse_input_value(laughing_man_shift_y_ratio_nr),
+            fit: OverlayFit::from_value(&get_select_value(overlay_fit_nr)),
+            laughing_man_z_index: parse_input_value(laughing_man_z_index_nr),
+            renderer: OverlayRenderer::from_value(&get_select_value(overlay_renderer_nr)),
+            ring_rotation_speed: parse_input_value(ring_rotation_speed_nr),
+            assignment: OverlayAssignment::from_value(&get_select_value(overlay_assignment_nr)),
+            smooth_motion: get_input_checked(smooth_motion_nr),
+            appear_transition: OverlayTransition::from_value(&get_select_value(
+                appear_transition_nr,
+            )),
+            disappear_transition: OverlayTransition::from_value(&get_select_value(
+                disappear_transition_nr,
+            )),
+            transition_duration: parse_input_value(transition_duration_nr),
         };
-        let is_active_laughing_man: bool = get_input_checked(is_active_laughing_man_nr);
+        let redaction_options = RedactionOptions {
+            style: RedactionStyle::from_value(&get_select_value(redaction_style_nr)),
+            blur_radius_ratio: parse_input_value(blur_radius_ratio_nr),
+            pixelate_block_ratio: parse_input_value(pixelate_block_ratio_nr),
+            solid_opacity: parse_input_value(solid_opacity_nr),
+            eye_bar_thickness_ratio: parse_input_value(eye_bar_thickness_ratio_nr),
+        };
+        let is_mirrored: bool = get_input_checked(is_mirrored_nr);
+        let object_fit = ObjectFit::from_css(&get_select_value(object_fit_nr));
         let interval_span: u32 = parse_input_value(interval_span_nr);
+        let overlay_settings = OverlaySettings {
+            image_data_urls: overlay_images.get_untracked(),
+            anchor: (
+                parse_input_value(overlay_anchor_x_nr),
+                parse_input_value(overlay_anchor_y_nr),
+            ),
+            aspect: OverlayAspect::from_value(&get_select_value(overlay_aspect_nr)),
+        };
+
+        let fail_safe_options = FailSafeOptions {
+            mode: FailSafeMode::from_value(&get_select_value(fail_safe_mode_nr)),
+            span_time_budget: parse_input_value(span_time_budget_nr),
+            center_region_ratio: parse_input_value(center_region_ratio_nr),
+            recovery_count: parse_input_value(recovery_count_nr),
+        };
+
+        let debug_view_options = DebugViewOptions {
+            raw_detections: get_input_checked(debug_raw_detections_nr),
+            tracked_boxes: get_input_checked(debug_tracked_boxes_nr),
+            labels: get_input_checked(debug_labels_nr),
+            landmarks: get_input_checked(debug_landmarks_nr),
+            trails: get_input_checked(debug_trails_nr),
+        };
 
         let canvas_app_options = CanvasAppOptions {
+            video_options,
             tracker_options,
             laughing_man_options,
-            is_active_laughing_man,
+            redaction_options,
+            is_mirrored,
+            object_fit,
             interval_span,
+            overlay_settings,
+            fail_safe_options,
+            debug_view_options,
+            show_performance_hud: get_input_checked(show_performance_hud_nr),
         };
         on_apply(canvas_app_options);
     };
 
     let default = move |_| {
         let CanvasAppOptions {
+            video_options,
             tracker_options,
             laughing_man_options,
-            is_active_laughing_man,
+            redaction_options,
+            is_mirrored,
+            object_fit,
             interval_span,
+            overlay_settings,
+            fail_safe_options,
+            debug_view_options,
+            show_performance_hud,
         } = CanvasAppOptions::default();
 
+        video_width_nrs.set(video_options.width);
+        video_height_nrs.set(video_options.height);
+        video_frame_rate_nrs.set(video_options.frame_rate);
+
         set_input_value_as_number(
             image_over_video_scale_nr,
             tracker_options.image_over_video_scale,
@@ -110,24 +359,157 @@ where
             slide_window_step_nr,
             tracker_options.slide_window_step as f64,
         );
-        set_input_checked(is_active_laughing_man_nr, is_active_laughing_man);
+        set_select_value(redaction_style_nr, redaction_options.style.to_value());
+        set_input_value_as_number(blur_radius_ratio_nr, redaction_options.blur_radius_ratio);
+        set_input_value_as_number(
+            pixelate_block_ratio_nr,
+            redaction_options.pixelate_block_ratio,
+        );
+        set_input_value_as_number(solid_opacity_nr, redaction_options.solid_opacity);
+        set_input_value_as_number(
+            eye_bar_thickness_ratio_nr,
+            redaction_options.eye_bar_thickness_ratio,
+        );
+        set_input_checked(is_mirrored_nr, is_mirrored);
+        set_select_value(object_fit_nr, object_fit.as_css());
         set_input_value_as_number(
             allowable_not_detect_count_nr,
             tracker_options.allowable_not_detect_count as f64,
         );
+        set_select_value(
+            roi_growth_mode_nr,
+            tracker_options.roi_growth.mode.to_value(),
+        );
+        set_input_value_as_number(roi_growth_rate_nr, tracker_options.roi_growth.rate);
+        set_input_value_as_number(
+            roi_growth_max_ratio_nr,
+            tracker_options.roi_growth.max_ratio,
+        );
+        set_input_value_as_number(trail_length_nr, tracker_options.trail_length as f64);
         set_input_value_as_number(
             laughing_man_size_ratio_nr,
             laughing_man_options.laughing_man_size_ratio,
         );
         set_input_value_as_number(
-            laughing_man_shift_ratio_nr,
-            laughing_man_options.laughing_man_shift_ratio,
+            laughing_man_shift_x_ratio_nr,
+            laughing_man_options.laughing_man_shift_x_ratio,
+        );
+        set_input_value_as_number(
+            laughing_man_shift_y_ratio_nr,
+            laughing_man_options.laughing_man_shift_y_ratio,
+        );
+        set_select_value(overlay_fit_nr, laughing_man_options.fit.to_value());
+        set_input_value_as_number(
+            laughing_man_z_index_nr,
+            laughing_man_options.laughing_man_z_index as f64,
+        );
+        set_select_value(
+            overlay_renderer_nr,
+            laughing_man_options.renderer.to_value(),
+        );
+        set_input_value_as_number(
+            ring_rotation_speed_nr,
+            laughing_man_options.ring_rotation_speed,
+        );
+        set_input_checked(smooth_motion_nr, laughing_man_options.smooth_motion);
+        set_select_value(
+            appear_transition_nr,
+            laughing_man_options.appear_transition.to_value(),
+        );
+        set_select_value(
+            disappear_transition_nr,
+            laughing_man_options.disappear_transition.to_value(),
+        );
+        set_input_value_as_number(
+            transition_duration_nr,
+            laughing_man_options.transition_duration,
         );
         set_input_value_as_number(interval_span_nr, interval_span as f64);
+        set_select_value(
+            overlay_assignment_nr,
+            laughing_man_options.assignment.to_value(),
+        );
+        overlay_images.set(overlay_settings.image_data_urls);
+        set_overlay_message.set(None);
+        set_input_value_as_number(overlay_anchor_x_nr, overlay_settings.anchor.0);
+        set_input_value_as_number(overlay_anchor_y_nr, overlay_settings.anchor.1);
+        set_select_value(overlay_aspect_nr, overlay_settings.aspect.to_value());
+        set_select_value(fail_safe_mode_nr, fail_safe_options.mode.to_value());
+        set_input_value_as_number(span_time_budget_nr, fail_safe_options.span_time_budget);
+        set_input_value_as_number(
+            center_region_ratio_nr,
+            fail_safe_options.center_region_ratio,
+        );
+        set_input_value_as_number(recovery_count_nr, fail_safe_options.recovery_count as f64);
+        set_input_checked(debug_raw_detections_nr, debug_view_options.raw_detections);
+        set_input_checked(debug_tracked_boxes_nr, debug_view_options.tracked_boxes);
+        set_input_checked(debug_labels_nr, debug_view_options.labels);
+        set_input_checked(debug_landmarks_nr, debug_view_options.landmarks);
+        set_input_checked(debug_trails_nr, debug_view_options.trails);
+        set_input_checked(show_performance_hud_nr, show_performance_hud);
+    };
+
+    // ユーザー画像の選択時に検証して集合に加える(適用するまでは反映しない)
+    let select_overlay_images = move |ev| {
+        let input = event_target::<web_sys::HtmlInputElement>(&ev);
+        let files: Vec<web_sys::File> = input
+            .files()
+            .map(|files| (0..files.length()).filter_map(|i| files.get(i)).collect())
+            .unwrap_or_default();
+        input.set_value(""); // 同じファイルを再度選択できるようにする
+
+        spawn_local(async move {
+            set_overlay_message.set(None);
+            for file in files {
+                match load_overlay_file(&file).await {
+                    Ok(image_data_url) => {
+                        overlay_images.update(|images| images.push(image_data_url));
+                    }
+                    Err(e) => {
+                        log::error!("{e}");
+                        set_overlay_message
+                            .set(Some(format!("{}を読み込めませんでした: {e}", file.name())));
+                    }
+                }
+            }
+        });
+    };
+
+    // カメラの選択はすぐに適用する
+    let select_camera = move |ev| {
+        on_select_camera(CameraSelection::from_value(&event_target_value(&ev)));
     };
 
     view! {cx,
         <div id="side-menu-container">
+            <label>
+                "カメラ:"
+                <select on:change=select_camera>
+                    <option value="default">"自動"</option>
+                    <option value="facing:user">"フロントカメラ"</option>
+                    <option value="facing:environment">"バックカメラ"</option>
+                    {
+                        move || {
+                            cameras.get().into_iter().map(|camera|{
+                                view!{cx,
+                                    <option value={CameraSelection::Device(camera.device_id).to_value()}>
+                                        {camera.label}
+                                    </option>
+                                }
+                            }).collect::<Vec<_>>()
+                        }
+                    }
+                </select>
+            </label>
+            <ConstraintRangeInput label="幅[px] 最小/理想/最大:" step=1.0
+                initial=video_options.width node_refs=video_width_nrs
+            />
+            <ConstraintRangeInput label="高さ[px] 最小/理想/最大:" step=1.0
+                initial=video_options.height node_refs=video_height_nrs
+            />
+            <ConstraintRangeInput label="フレームレート[fps] 最小/理想/最大:" step=1.0
+                initial=video_options.frame_rate node_refs=video_frame_rate_nrs
+            />
             <label>
                 "ビデオサイズに対する画像比[0.1, 1]:"
                 <input type="number" min=0.1 max=1 step=0.01
@@ -164,11 +546,70 @@ where
                 />
             </label>
             <label>
-                "笑い男モード:"
-                <input type="checkbox" checked={is_active_laughing_man}
-                    node_ref=is_active_laughing_man_nr
+                "匿名化の方法:"
+                <select node_ref=redaction_style_nr>
+                    {
+                        [
+                            (RedactionStyle::LaughingMan, "笑い男"),
+                            (RedactionStyle::Blur, "ぼかし"),
+                            (RedactionStyle::Pixelate, "モザイク"),
+                            (RedactionStyle::SolidBox, "塗りつぶし"),
+                            (RedactionStyle::EyeBar, "目線"),
+                            (RedactionStyle::Outline, "枠のみ(確認用)"),
+                        ].into_iter().map(|(value, label)| view!{cx,
+                            <option value={value.to_value()} selected={value == redaction_options.style}>{label}</option>
+                        }).collect::<Vec<_>>()
+                    }
+                </select>
+            </label>
+            <label>
+                "ぼかしの半径(顔の幅に対する比率):"
+                <input type="number" min=0 step=0.01
+                    value={redaction_options.blur_radius_ratio.to_string()}
+                    node_ref=blur_radius_ratio_nr
+                />
+            </label>
+            <label>
+                "モザイクの大きさ(顔の幅に対する比率):"
+                <input type="number" min=0 step=0.01
+                    value={redaction_options.pixelate_block_ratio.to_string()}
+                    node_ref=pixelate_block_ratio_nr
+                />
+            </label>
+            <label>
+                "塗りつぶしの不透明度[0.0, 1.0]:"
+                <input type="number" min=0 max=1 step=0.05
+                    value={redaction_options.solid_opacity.to_string()}
+                    node_ref=solid_opacity_nr
+                />
+            </label>
+            <label>
+                "目線の太さ(顔の高さに対する比率):"
+                <input type="number" min=0 step=0.01
+                    value={redaction_options.eye_bar_thickness_ratio.to_string()}
+                    node_ref=eye_bar_thickness_ratio_nr
+                />
+            </label>
+            <label>
+                "左右反転(セルフィー)表示:"
+                <input type="checkbox" checked={is_mirrored}
+                    node_ref=is_mirrored_nr
                 />
             </label>
+            <label>
+                "表示の配置:"
+                <select node_ref=object_fit_nr>
+                    {
+                        [
+                            (ObjectFit::Contain, "全体を表示"),
+                            (ObjectFit::Cover, "画面を覆う(切り取り)"),
+                            (ObjectFit::Fill, "引き伸ばす"),
+                        ].into_iter().map(|(value, label)| view!{cx,
+                            <option value={value.as_css()} selected={value == object_fit}>{label}</option>
+                        }).collect::<Vec<_>>()
+                    }
+                </select>
+            </label>
             <label>
                 "トラッキング許容カウント[1, ):"
                 <input type="number" min=1
@@ -176,6 +617,34 @@ where
                     node_ref=allowable_not_detect_count_nr
                 />
             </label>
+            <label>
+                "見失っている間に覆う範囲を広げる:"
+                <select node_ref=roi_growth_mode_nr>
+                    {
+                        [
+                            (RoiGrowthMode::Off, "広げない"),
+                            (RoiGrowthMode::Velocity, "動きの速さに合わせる"),
+                            (RoiGrowthMode::Rate, "一定の割合"),
+                        ].into_iter().map(|(value, label)| view!{cx,
+                            <option value={value.to_value()} selected={value == tracker_options.roi_growth.mode}>{label}</option>
+                        }).collect::<Vec<_>>()
+                    }
+                </select>
+            </label>
+            <label>
+                "広げる割合(1回の検出あたり)[0.0, ):"
+                <input type="number" min=0 step=0.01
+                    value={tracker_options.roi_growth.rate.to_string()}
+                    node_ref=roi_growth_rate_nr
+                />
+            </label>
+            <label>
+                "広げる大きさの上限(倍)[1.0, ):"
+                <input type="number" min=1 step=0.1
+                    value={tracker_options.roi_growth.max_ratio.to_string()}
+                    node_ref=roi_growth_max_ratio_nr
+                />
+            </label>
             <label>
                 "笑い男拡大係数(0.0, ):"
                 <input type="number" min=0 step=0.01
@@ -184,12 +653,228 @@ where
                 />
             </label>
             <label>
-                "笑い男シフト係数( , ):"
+                "笑い男シフト係数(顔の幅・高さに対する比率 x, y)( , ):"
+                <input type="number" step=0.01
+                    value={laughing_man_options.laughing_man_shift_x_ratio.to_string()}
+                    node_ref=laughing_man_shift_x_ratio_nr
+                />
                 <input type="number" step=0.01
-                    value={laughing_man_options.laughing_man_shift_ratio.to_string()}
-                    node_ref=laughing_man_shift_ratio_nr
+                    value={laughing_man_options.laughing_man_shift_y_ratio.to_string()}
+                    node_ref=laughing_man_shift_y_ratio_nr
+                />
+            </label>
+            <label>
+                "笑い男の顔への合わせ方(縦横比を保つ場合):"
+                <select node_ref=overlay_fit_nr>
+                    {
+                        [
+                            (OverlayFit::FitHeight, "高さを合わせる"),
+                            (OverlayFit::FitWidth, "幅を合わせる"),
+                            (OverlayFit::Cover, "顔を覆う"),
+                            (OverlayFit::Contain, "顔に収める"),
+                        ].into_iter().map(|(value, label)| view!{cx,
+                            <option value={value.to_value()} selected={value == laughing_man_options.fit}>{label}</option>
+                        }).collect::<Vec<_>>()
+                    }
+                </select>
+            </label>
+            <label>
+                "笑い男z-index(キャンバスの場合は3より大きいと文字の上に描画):"
+                <input type="number" min=0 step=1
+                    value={laughing_man_options.laughing_man_z_index.to_string()}
+                    node_ref=laughing_man_z_index_nr
                 />
             </label>
+            <label>
+                "笑い男の描画方法:"
+                <select node_ref=overlay_renderer_nr>
+                    {
+                        [
+                            (OverlayRenderer::Dom, "画像要素(DOM)"),
+                            (OverlayRenderer::Canvas, "キャンバス"),
+                        ].into_iter().map(|(value, label)| view!{cx,
+                            <option value={value.to_value()} selected={value == laughing_man_options.renderer}>{label}</option>
+                        }).collect::<Vec<_>>()
+                    }
+                </select>
+            </label>
+            <label>
+                "笑い男の文字の回転速度(時計回りが正) [deg/s]:"
+                <input type="number" step=1
+                    value={laughing_man_options.ring_rotation_speed.to_string()}
+                    node_ref=ring_rotation_speed_nr
+                />
+            </label>
+            <label>
+                "重ねる画像(PNG/SVG):"
+                <input type="file" accept="image/png,image/svg+xml" multiple on:change=select_overlay_images/>
+            </label>
+            <div>
+                {
+                    move || overlay_message.get().unwrap_or_else(|| {
+                        match overlay_images.with(Vec::len) {
+                            0 => "笑い男".to_string(),
+                            count => format!("ユーザー画像: {count}枚"),
+                        }
+                    })
+                }
+                <button on:click=move |_| {
+                    overlay_images.set(Vec::new());
+                    set_overlay_message.set(None);
+                }>"笑い男に戻す"</button>
+            </div>
+            <label>
+                "画像の割り当て方法:"
+                <select node_ref=overlay_assignment_nr>
+                    {
+                        [
+                            (OverlayAssignment::RoundRobin, "順番"),
+                            (OverlayAssignment::Random, "ランダム"),
+                            (OverlayAssignment::Manual, "手動(顔をクリックで切り替え)"),
+                        ].into_iter().map(|(value, label)| view!{cx,
+                            <option value={value.to_value()} selected={value == laughing_man_options.assignment}>{label}</option>
+                        }).collect::<Vec<_>>()
+                    }
+                </select>
+            </label>
+            <label>
+                "ユーザー画像の基準点(顔の中心に合わせる位置の比率 x, y):"
+                <input type="number" min=0 max=1 step=0.01
+                    value={overlay_settings.anchor.0.to_string()}
+                    node_ref=overlay_anchor_x_nr
+                />
+                <input type="number" min=0 max=1 step=0.01
+                    value={overlay_settings.anchor.1.to_string()}
+                    node_ref=overlay_anchor_y_nr
+                />
+            </label>
+            <label>
+                "ユーザー画像の縦横比:"
+                <select node_ref=overlay_aspect_nr>
+                    {
+                        [
+                            (OverlayAspect::Keep, "保つ(笑い男の合わせ方に従う)"),
+                            (OverlayAspect::Stretch, "顔に合わせて引き伸ばす"),
+                        ].into_iter().map(|(value, label)| view!{cx,
+                            <option value={value.to_value()} selected={value == overlay_settings.aspect}>{label}</option>
+                        }).collect::<Vec<_>>()
+                    }
+                </select>
+            </label>
+            <label>
+                "笑い男や匿名化を検出の間も滑らかに動かす:"
+                <input type="checkbox" checked={laughing_man_options.smooth_motion}
+                    node_ref=smooth_motion_nr
+                />
+            </label>
+            <label>
+                "笑い男が現れる時のアニメーション(画像要素の場合):"
+                <select node_ref=appear_transition_nr>
+                    {
+                        [
+                            (OverlayTransition::None, "なし"),
+                            (OverlayTransition::Fade, "フェードイン"),
+                            (OverlayTransition::Pop, "拡大"),
+                        ].into_iter().map(|(value, label)| view!{cx,
+                            <option value={value.to_value()} selected={value == laughing_man_options.appear_transition}>{label}</option>
+                        }).collect::<Vec<_>>()
+                    }
+                </select>
+            </label>
+            <label>
+                "笑い男が消える時のアニメーション(画像要素の場合):"
+                <select node_ref=disappear_transition_nr>
+                    {
+                        [
+                            (OverlayTransition::None, "なし"),
+                            (OverlayTransition::Fade, "フェードアウト"),
+                            (OverlayTransition::Pop, "縮小"),
+                        ].into_iter().map(|(value, label)| view!{cx,
+                            <option value={value.to_value()} selected={value == laughing_man_options.disappear_transition}>{label}</option>
+                        }).collect::<Vec<_>>()
+                    }
+                </select>
+            </label>
+            <label>
+                "アニメーション時間[0, ) [ms]:"
+                <input type="number" min=0 step=10
+                    value={laughing_man_options.transition_duration.to_string()}
+                    node_ref=transition_duration_nr
+                />
+            </label>
+            <label>
+                "追跡が不安定な時に映像を隠す:"
+                <select node_ref=fail_safe_mode_nr>
+                    {
+                        [
+                            (FailSafeMode::Off, "隠さない"),
+                            (FailSafeMode::Blank, "黒く塗りつぶす"),
+                            (FailSafeMode::Blur, "全体をぼかす"),
+                        ].into_iter().map(|(value, label)| view!{cx,
+                            <option value={value.to_value()} selected={value == fail_safe_options.mode}>{label}</option>
+                        }).collect::<Vec<_>>()
+                    }
+                </select>
+            </label>
+            <label>
+                "隠すまでの検出時間の上限(0, ) [ms]:"
+                <input type="number" min=0 step=10
+                    value={fail_safe_options.span_time_budget.to_string()}
+                    node_ref=span_time_budget_nr
+                />
+            </label>
+            <label>
+                "見失うと隠す中央の領域の大きさ[0.0, 1.0]:"
+                <input type="number" min=0 max=1 step=0.05
+                    value={fail_safe_options.center_region_ratio.to_string()}
+                    node_ref=center_region_ratio_nr
+                />
+            </label>
+            <label>
+                "再び表示するまでの安定した検出の回数[1, ):"
+                <input type="number" min=1 step=1
+                    value={fail_safe_options.recovery_count.to_string()}
+                    node_ref=recovery_count_nr
+                />
+            </label>
+            <label>
+                "枠の表示(確認用)で検出器の矩形とスコアを描く:"
+                <input type="checkbox" checked={debug_view_options.raw_detections}
+                    node_ref=debug_raw_detections_nr
+                />
+            </label>
+            <label>
+                "枠の表示(確認用)で追跡中の矩形を描く:"
+                <input type="checkbox" checked={debug_view_options.tracked_boxes}
+                    node_ref=debug_tracked_boxes_nr
+                />
+            </label>
+            <label>
+                "枠の表示(確認用)でID・状態・回転角を描く:"
+                <input type="checkbox" checked={debug_view_options.labels}
+                    node_ref=debug_labels_nr
+                />
+            </label>
+            <label>
+                "枠の表示(確認用)で特徴点を描く:"
+                <input type="checkbox" checked={debug_view_options.landmarks}
+                    node_ref=debug_landmarks_nr
+                />
+            </label>
+            <label>
+                "枠の表示(確認用)で軌跡を描く:"
+                <input type="checkbox" checked={debug_view_options.trails}
+                    node_ref=debug_trails_nr
+                />
+            </label>
+            <label>
+                "軌跡として保持する位置の数[0, ):"
+                <input type="number" min=0 step=1
+                    value={tracker_options.trail_length.to_string()}
+                    node_ref=trail_length_nr
+                />
+            </label>
+            <button on:click=move |_| on_export_trajectories()>"軌跡をCSVで保存"</button>
             <label>
                 "描画インターバル時間(10, ) [ms]:"
                 <input type="number" step=10
@@ -197,6 +882,12 @@ where
                     node_ref=interval_span_nr
                 />
             </label>
+            <label>
+                "検出の処理時間などを表示する:"
+                <input type="checkbox" checked={show_performance_hud}
+                    node_ref=show_performance_hud_nr
+                />
+            </label>
             <div id="default-apply-button">
                 <button id="default-button" on:click=default>"デフォルト"</button>
                 <button id="apply-button" on:click=apply>"適用"</button>
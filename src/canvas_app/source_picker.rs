@@ -0,0 +1,83 @@
+use crate::canvas_app::VideoSource;
+
+use leptos::*;
+use std::rc::Rc;
+
+/// 選択されたファイルのオブジェクトURLを作成する
+fn selected_file_object_url(ev: &web_sys::Event) -> Option<String> {
+    let input = event_target::<web_sys::HtmlInputElement>(ev);
+    let object_url = input
+        .files()
+        .and_then(|files| files.get(0))
+        .and_then(
+            |file| match web_sys::Url::create_object_url_with_blob(&file) {
+                Ok(object_url) => Some(object_url),
+                Err(e) => {
+                    log::error!("{e:?}");
+                    None
+                }
+            },
+        );
+    input.set_value(""); // 同じファイルを再度選択できるようにする
+    object_url
+}
+
+/// 入力ソース(ビデオまたは静止画)を選択するコンポーネント
+#[component]
+pub fn SourcePicker<F, G>(cx: Scope, on_select_source: F, on_select_image: G) -> impl IntoView
+where
+    F: Fn(VideoSource) + 'static,
+    G: Fn(String) + 'static,
+{
+    let on_select_source = Rc::new(on_select_source);
+
+    let select_camera = {
+        let on_select_source = on_select_source.clone();
+        move |_| {
+            on_select_source(VideoSource::default());
+        }
+    };
+
+    let select_screen = {
+        let on_select_source = on_select_source.clone();
+        move |_| {
+            on_select_source(VideoSource::Screen);
+        }
+    };
+
+    let select_synthetic = {
+        let on_select_source = on_select_source.clone();
+        move |_| {
+            on_select_source(VideoSource::Synthetic);
+        }
+    };
+
+    let select_file = move |ev| {
+        if let Some(object_url) = selected_file_object_url(&ev) {
+            on_select_source(VideoSource::File(object_url));
+        }
+    };
+
+    let select_image = move |ev| {
+        if let Some(object_url) = selected_file_object_url(&ev) {
+            on_select_image(object_url);
+        }
+    };
+
+    view! {cx,
+        <div class="source-picker">
+            "入力:"
+            <button on:click=select_camera>"カメラ"</button>
+            <button on:click=select_screen>"画面共有"</button>
+            <button on:click=select_synthetic>"合成映像"</button>
+            <label class="file-button">
+                "動画ファイル"
+                <input type="file" accept="video/*" on:change=select_file/>
+            </label>
+            <label class="file-button">
+                "画像ファイル"
+                <input type="file" accept="image/png,image/jpeg" on:change=select_image/>
+            </label>
+        </div>
+    }
+}
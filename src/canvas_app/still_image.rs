@@ -0,0 +1,240 @@
+use super::dom_utils::{context2d, create_hidden_canvas, download_url, load_image};
+use super::geometry::{Rect, Transform};
+use super::laughing_man_image::{
+    LaughingManCanvasRenderer, LaughingManOptions, LaughingManState, OverlayLayers,
+};
+use super::overlay_assignment::{OverlayAssigner, OverlayAssignment};
+use super::redaction::{redact, RedactionOptions, RedactionStyle};
+use super::video_face_tracker::FaceLandmarks;
+use crate::error::AppError;
+
+use leptos::*;
+use rustface::Rectangle;
+
+/// 静止画モードの画像と検出結果
+pub struct StillImage {
+    /// アップロードされた画像
+    image: web_sys::HtmlImageElement,
+    /// 画像のオブジェクトURL
+    object_url: String,
+    /// 重ねる笑い男画像の集合
+    overlays: Vec<LaughingManCanvasRenderer>,
+    /// 検出された顔(元の画像の座標)
+    faces: Vec<Rectangle>,
+    /// 各顔の特徴点(元の画像の座標)
+    landmarks: Vec<Option<FaceLandmarks>>,
+    /// 各顔に割り当てた画像の番号
+    overlay_indices: Vec<usize>,
+}
+
+impl StillImage {
+    /// 画像と笑い男画像の集合を読み込む
+    pub async fn load(object_url: String, layer_set: &[OverlayLayers]) -> Result<Self, AppError> {
+        let image = load_image(&object_url).await?;
+        let overlays = LaughingManCanvasRenderer::load_all(layer_set).await?;
+        Ok(Self {
+            image,
+            object_url,
+            overlays,
+            faces: Vec::new(),
+            landmarks: Vec::new(),
+            overlay_indices: Vec::new(),
+        })
+    }
+
+    /// 元の画像
+    pub fn image(&self) -> &web_sys::HtmlImageElement {
+        &self.image
+    }
+
+    /// 元の画像のサイズ(width, height)
+    pub fn size(&self) -> (u32, u32) {
+        (self.image.natural_width(), self.image.natural_height())
+    }
+
+    /// 検出された顔
+    pub fn faces(&self) -> &[Rectangle] {
+        &self.faces
+    }
+
+    /// 検出結果を設定し，各顔に割り当て方法に従って画像を割り当てる．
+    /// プレビューと保存する画像が同じになるように，割り当ては検出結果を設定したときに決める
+    pub fn set_faces(
+        &mut self,
+        faces: Vec<Rectangle>,
+        landmarks: Vec<Option<FaceLandmarks>>,
+        assignment: OverlayAssignment,
+    ) {
+        let mut overlay_assigner = OverlayAssigner::default();
+        self.overlay_indices = (0..faces.len())
+            .map(|i| overlay_assigner.assign(i as u32, self.overlays.len(), assignment))
+            .collect();
+        self.faces = faces;
+        self.landmarks = landmarks;
+    }
+
+    /// 画像に選択した顔を匿名化して描画する
+    /// - transform: 元の画像の座標から描画先の座標への変換．左右反転を含む場合も笑い男画像自体は反転しない
+    /// - selected: 各顔を匿名化するかどうか．匿名化の方法が枠の場合は匿名化しない
+    /// - show_unselected: 匿名化しない顔を枠で表示するかどうか(確認用)
+    pub fn draw(
+        &self,
+        context: &web_sys::CanvasRenderingContext2d,
+        transform: &Transform,
+        selected: &[bool],
+        options: &LaughingManOptions,
+        redaction: &RedactionOptions,
+        show_unselected: bool,
+    ) -> Result<(), AppError> {
+        let (width, height) = self.size();
+
+        context.save();
+        let draw_res = transform.apply_to_context(context).and_then(|_| {
+            context.draw_image_with_html_image_element_and_dw_and_dh(
+                &self.image,
+                0.0,
+                0.0,
+                width as f64,
+                height as f64,
+            )
+        });
+        context.restore();
+        draw_res?;
+
+        for (i, face) in self.faces.iter().enumerate() {
+            let state = LaughingManState {
+                id: i as u32,
+                rect: transform.apply_rect(&Rect::from(face)),
+            };
+
+            let is_redacted = selected.get(i).copied().unwrap_or(true)
+                && redaction.style != RedactionStyle::Outline;
+            let overlay = self
+                .overlay_indices
+                .get(i)
+                .and_then(|index| self.overlays.get(*index));
+            if is_redacted && redaction.style == RedactionStyle::LaughingMan {
+                match overlay {
+                    // 静止画では外周の文字は初期位相のまま
+                    Some(overlay) => overlay.draw_state(context, &state, options, 0.0)?,
+                    None => redact(context, &state.rect, None, &redaction.opaque_solid_box())?,
+                }
+            } else if is_redacted {
+                // ぼかしとモザイクは描画済みの画像の画素を加工する
+                let eyes = self
+                    .landmarks
+                    .get(i)
+                    .and_then(Option::as_ref)
+                    .map(|landmarks| {
+                        (
+                            transform.apply_point(landmarks.left_eye.x, landmarks.left_eye.y),
+                            transform.apply_point(landmarks.right_eye.x, landmarks.right_eye.y),
+                        )
+                    });
+                redact(context, &state.rect, eyes, redaction)?;
+            } else if show_unselected {
+                context.stroke_rect(
+                    state.rect.x,
+                    state.rect.y,
+                    state.rect.width,
+                    state.rect.height,
+                );
+            }
+            if show_unselected {
+                context.fill_text(&format!("{}", i + 1), state.rect.x, state.rect.y)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 元の解像度で合成した画像をPNGとしてダウンロードする
+    /// - is_mirrored: 左右反転して保存するかどうか
+    pub fn download_png(
+        &self,
+        is_mirrored: bool,
+        selected: &[bool],
+        options: &LaughingManOptions,
+        redaction: &RedactionOptions,
+        file_name: &str,
+    ) -> Result<(), AppError> {
+        let (width, height) = self.size();
+        let export_canvas = create_hidden_canvas(width, height)?;
+        let transform = if is_mirrored {
+            Transform::mirror_x(width as f64)
+        } else {
+            Transform::identity()
+        };
+        self.draw(
+            &context2d(&export_canvas)?,
+            &transform,
+            selected,
+            options,
+            redaction,
+            false,
+        )?;
+
+        let data_url = export_canvas.to_data_url_with_type("image/png")?;
+        download_url(&data_url, file_name)
+    }
+}
+
+impl Drop for StillImage {
+    fn drop(&mut self) {
+        if let Err(e) = web_sys::Url::revoke_object_url(&self.object_url) {
+            log::error!("{e:?}");
+        }
+    }
+}
+
+/// 静止画モードで検出結果を確認してダウンロードするパネル
+#[component]
+pub fn StillImagePanel<F>(
+    cx: Scope,
+    selected: RwSignal<Vec<bool>>,
+    export_mirrored: RwSignal<bool>,
+    on_download: F,
+) -> impl IntoView
+where
+    F: Fn() + 'static,
+{
+    view! {cx,
+        <div class="still-image-panel">
+            {
+                move || {
+                    let face_count = selected.with(|selected| selected.len());
+                    if face_count == 0 {
+                        view!{cx, <span>"顔が検出されませんでした"</span>}.into_view(cx)
+                    } else {
+                        (0..face_count).map(|i| {
+                            let toggle = move |ev| {
+                                let checked = event_target_checked(&ev);
+                                selected.update(|selected| {
+                                    if let Some(face_selected) = selected.get_mut(i) {
+                                        *face_selected = checked;
+                                    }
+                                });
+                            };
+                            view!{cx,
+                                <label>
+                                    {format!("顔{}:", i + 1)}
+                                    <input type="checkbox"
+                                        prop:checked=move || selected.with(|selected| selected.get(i).copied().unwrap_or(false))
+                                        on:change=toggle
+                                    />
+                                </label>
+                            }
+                        }).collect::<Vec<_>>().into_view(cx)
+                    }
+                }
+            }
+            <label>
+                "左右反転して保存:"
+                <input type="checkbox"
+                    prop:checked=move || export_mirrored.get()
+                    on:change=move |ev| export_mirrored.set(event_target_checked(&ev))
+                />
+            </label>
+            <button on:click=move |_| on_download()>"PNGで保存"</button>
+        </div>
+    }
+}
@@ -1,43 +1,65 @@
+mod camera;
 mod face_detection;
+mod landmark;
+mod synthetic;
 mod tracker;
 mod tracker_roi;
+mod video_source;
 
-use super::dom_utils::{canvas, context2d, document, window};
+use super::dom_utils::{canvas, context2d, create_hidden_canvas, window};
+use super::geometry::{Rect, Transform};
 use crate::error::AppError;
 use face_detection::{convert_rgba_to_luma, detect_faces};
+use landmark::estimate_landmarks;
+pub use landmark::FaceLandmarks;
 use tracker::Tracker;
-use tracker_roi::TrackerRoi;
+use tracker_roi::trajectories_csv;
+pub use tracker_roi::{FaceEstimate, RoiGrowthMode, RoiGrowthOptions, RoiSnapshot, TrackerRoi};
+use video_source::{attach_source, detach_source, is_stream_ended, release_source, SourceStream};
+
+pub use camera::{
+    apply_video_constraints, enumerate_cameras, CameraDevice, CameraSelection, ConstraintRange,
+    VideoOptions,
+};
+pub use synthetic::{check_tracking, TrackingCheck};
+pub use video_source::{open_source, VideoSource};
 
 use bytes::Buf;
 use rustface::{read_model, Detector, Rectangle};
-use wasm_bindgen::{JsCast, JsValue};
 use wasm_bindgen_futures::JsFuture;
 
-/// ビデオ要素をwebカメラと連動させる初期化のための関数
-async fn initialize_video(video: &web_sys::HtmlVideoElement) -> Result<(), AppError> {
-    let mut media_constraints = web_sys::MediaStreamConstraints::new();
-    media_constraints.audio(&JsValue::FALSE);
-    media_constraints.video(&JsValue::TRUE);
-
-    let stream_promise = window()
-        .navigator()
-        .media_devices()?
-        .get_user_media_with_constraints(&media_constraints)?;
-
-    let stream = JsFuture::from(stream_promise)
-        .await?
-        .dyn_into::<web_sys::MediaStream>()
-        .map_err(|js_value| {
-            AppError::JsError(format!(
-                "Sanity Check. Cannot convert from: {js_value:?} into web_sys::MediaStream"
-            ))
-        })?;
-
-    video.set_src_object(Some(&stream));
+/// 再生位置が経過時間からの見込みよりこの時間[s]以上先に進んだ場合(または戻った場合)はシークとみなす
+const SEEK_JUMP_TOLERANCE: f64 = 1.0;
+
+/// 前回のステップからの再生位置の変化がシークやループによるものかどうか．
+/// インターバルの間隔が長い場合も誤判定しないように，実際の経過時間に再生速度を掛けた見込みと比べる．
+/// 読み込みの待ちなどで遅れる場合はシークとみなさない
+/// - media_elapsed: 再生位置の変化[s]
+/// - wall_elapsed: 実際の経過時間[s]
+fn is_seek_jump(media_elapsed: f64, wall_elapsed: f64, playback_rate: f64) -> bool {
+    media_elapsed < 0.0 || media_elapsed - wall_elapsed * playback_rate > SEEK_JUMP_TOLERANCE
+}
+
+/// 静止画から検出する際の入力画像の長辺の最大値[px]
+const STILL_IMAGE_MAX_SIDE: f64 = 800.0;
+
+/// ビデオを再生する．再生が始まるとビデオのサイズが取得できる
+pub async fn play_video(video: &web_sys::HtmlVideoElement) -> Result<(), AppError> {
     JsFuture::from(video.play()?).await?;
     Ok(())
 }
 
+/// ビデオのサイズとスケールから検出器への入力画像のサイズを計算
+fn image_size_from_video(
+    video: &web_sys::HtmlVideoElement,
+    image_over_video_scale: f64,
+) -> (u32, u32) {
+    (
+        (video.video_width() as f64 * image_over_video_scale) as u32,
+        (video.video_height() as f64 * image_over_video_scale) as u32,
+    )
+}
+
 /// モデルをフェッチして検出器を作成
 async fn fetch_and_create_detector(url: &str) -> Result<Box<dyn Detector>, AppError> {
     let res_bytes = reqwest::get(url).await?.bytes().await?;
@@ -62,6 +84,10 @@ pub struct TrackerOptions {
     pub slide_window_step: u32,
     /// トラッカーのallowable_not_detect_count
     pub allowable_not_detect_count: u32,
+    /// 検出されない間に覆う範囲を広げる方法
+    pub roi_growth: RoiGrowthOptions,
+    /// 各トラックが保持する位置の履歴(軌跡)の数
+    pub trail_length: usize,
 }
 
 impl Default for TrackerOptions {
@@ -73,6 +99,8 @@ impl Default for TrackerOptions {
             pyramid_scale_factor: 0.5,
             slide_window_step: 4,
             allowable_not_detect_count: 4,
+            roi_growth: Default::default(),
+            trail_length: 30,
         }
     }
 }
@@ -83,21 +111,56 @@ pub struct VideoFaceTracker {
     make_image_context: web_sys::CanvasRenderingContext2d,
     /// 画像を取得するビデオ
     stream_video: web_sys::HtmlVideoElement,
+    /// ビデオの入力ソース
+    source: VideoSource,
+    /// ソースのストリーム
+    source_stream: SourceStream,
+    /// ライフサイクルの状態
+    lifecycle_state: LifecycleState,
+    /// 前回のステップでの再生位置[s]と時刻[ms]
+    last_playback: Option<(f64, f64)>,
+    /// ストリームのオプション
+    video_options: VideoOptions,
     /// 検出器への入力画像のサイズ(width, height)
     image_size: (u32, u32),
     /// 検出器
     detector: Box<dyn Detector>,
     /// トラッカー
     tracker: Tracker,
+    /// 初期化で削除したRoi．次のstepで削除されたRoiとして返す
+    reset_rois: Vec<TrackerRoi>,
     /// パフォーマンス
     performance: web_sys::Performance,
     /// トラッカーオプション
     tracker_option: TrackerOptions,
 }
 
+/// VideoFaceTrackerのライフサイクルの状態
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum LifecycleState {
+    /// トラッキング中
+    Running,
+    /// 一時停止中
+    Paused,
+    /// 終了済み．ソースを解放している
+    Shutdown,
+}
+
+/// 検出器が出力した顔(トラッカーに渡す前)
+#[derive(Clone, Debug)]
+pub struct RawDetection {
+    /// 入力画像の座標の矩形
+    pub bbox: Rectangle,
+    pub score: f64,
+}
+
 /// ビデオトラッカーの返す情報
 pub struct VideoFaceInfo<'a> {
     pub rois: Vec<&'a TrackerRoi>,
+    /// 各roiの特徴点と回転角．roisと同じ順番
+    pub estimates: Vec<FaceEstimate>,
+    /// 今回の更新で検出器が出力した顔．フレームが無く検出しなかった場合は空
+    pub detections: Vec<RawDetection>,
     pub added_rois: Vec<TrackerRoi>,
     pub removed_rois: Vec<TrackerRoi>,
     pub span_time: f64,
@@ -105,35 +168,35 @@ pub struct VideoFaceInfo<'a> {
 
 impl VideoFaceTracker {
     /// コンストラクタ
-    /// - image_over_video_scale: 入力画像 / ビデオ のスケール
+    /// - source: ビデオの入力ソース．カメラを開けない場合(カメラの無い環境や許可されない場合)は合成映像にする
+    /// - video_options: カメラのストリームの解像度やフレームレート
     pub async fn new(
         stream_video: web_sys::HtmlVideoElement,
         model_url: String,
+        source: VideoSource,
+        video_options: VideoOptions,
         tracker_option: TrackerOptions,
     ) -> Result<VideoFaceTracker, AppError> {
         // ビデオの初期化
-        initialize_video(&stream_video).await?;
+        let (source, source_stream) = match open_source(&source, &video_options).await {
+            Ok(source_stream) => (source, source_stream),
+            Err(e) if matches!(source, VideoSource::Camera(_)) => {
+                log::error!("{e}");
+                let source = VideoSource::Synthetic;
+                let source_stream = open_source(&source, &video_options).await?;
+                (source, source_stream)
+            }
+            Err(e) => return Err(e),
+        };
+        attach_source(&stream_video, &source, source_stream.media_stream());
+        play_video(&stream_video).await?;
 
         // ビデオからサイズを取得
-        let (video_width, video_height) = (stream_video.video_width(), stream_video.video_height());
-        let (image_width, image_height) = (
-            (video_width as f64 * tracker_option.image_over_video_scale) as u32,
-            (video_height as f64 * tracker_option.image_over_video_scale) as u32,
-        );
+        let (image_width, image_height) =
+            image_size_from_video(&stream_video, tracker_option.image_over_video_scale);
 
         // 画像作成用のキャンパスを作成する
-        let make_image_canvas = document()
-            .create_element("canvas")?
-            .dyn_into::<web_sys::HtmlCanvasElement>()
-            .map_err(|element| {
-                AppError::DomError(format!(
-                    "Cannot convert from element: {element:?}, into HtmlCanvasElement"
-                ))
-            })?;
-        make_image_canvas.set_width(image_width);
-        make_image_canvas.set_height(image_height);
-        let make_image_canvas_style = make_image_canvas.style();
-        make_image_canvas_style.set_css_text("display:none");
+        let make_image_canvas = create_hidden_canvas(image_width, image_height)?;
         let make_image_context = context2d(&make_image_canvas)?;
 
         // 検出器を作成
@@ -148,7 +211,10 @@ impl VideoFaceTracker {
         );
 
         // トラッカー
-        let tracker = Tracker::new(tracker_option.allowable_not_detect_count);
+        let tracker = Tracker::new(
+            tracker_option.allowable_not_detect_count,
+            tracker_option.trail_length,
+        );
 
         // パフォーマンス
         let performance = window()
@@ -158,9 +224,15 @@ impl VideoFaceTracker {
         Ok(Self {
             make_image_context,
             stream_video,
+            source,
+            source_stream,
+            lifecycle_state: LifecycleState::Running,
+            last_playback: None,
+            video_options,
             image_size: (image_width, image_height),
             detector,
             tracker,
+            reset_rois: Vec::new(),
             performance,
             tracker_option,
         })
@@ -169,6 +241,30 @@ impl VideoFaceTracker {
     /// ビデオトラッカーを遷移
     pub fn step(&mut self) -> Result<VideoFaceInfo<'_>, AppError> {
         let start_time = self.performance.now();
+        let mut removed_rois = std::mem::take(&mut self.reset_rois);
+
+        // 一時停止中やストリームの切り替え中などでフレームが無い場合はトラッカーを遷移させない
+        if self.lifecycle_state != LifecycleState::Running
+            || self.stream_video.ready_state() < web_sys::HtmlMediaElement::HAVE_CURRENT_DATA
+        {
+            // 止まっている間の経過時間は見込みに含めない(止まっている間のシークを検出できるように)
+            self.last_playback = self
+                .last_playback
+                .map(|(last_current_time, _)| (last_current_time, start_time));
+            return Ok(VideoFaceInfo {
+                rois: self.tracker.rois().iter().collect(),
+                estimates: self
+                    .tracker
+                    .rois()
+                    .iter()
+                    .map(TrackerRoi::estimate)
+                    .collect(),
+                detections: Vec::new(),
+                added_rois: Vec::new(),
+                removed_rois,
+                span_time: self.performance.now() - start_time,
+            });
+        }
 
         self.make_image_context
             .draw_image_with_html_video_element_and_dw_and_dh(
@@ -179,6 +275,22 @@ impl VideoFaceTracker {
                 self.image_size.1 as f64,
             )?;
 
+        // シークやループで再生位置が飛んだ場合はトラッカーを初期化
+        let current_time = self.stream_video.current_time();
+        let now = self.performance.now();
+        if let Some((last_current_time, last_now)) = self.last_playback {
+            if self.source.is_seekable()
+                && is_seek_jump(
+                    current_time - last_current_time,
+                    (now - last_now) / 1000.0,
+                    self.stream_video.playback_rate(),
+                )
+            {
+                removed_rois.extend(self.tracker.reset());
+            }
+        }
+        self.last_playback = Some((current_time, now));
+
         let image_vec = self
             .make_image_context
             .get_image_data(0.0, 0.0, self.image_size.0 as f64, self.image_size.1 as f64)?
@@ -193,36 +305,107 @@ impl VideoFaceTracker {
             self.image_size.0,
             self.image_size.1,
         );
+        let detections = faces
+            .iter()
+            .map(|face| RawDetection {
+                bbox: *face.bbox(),
+                score: face.score(),
+            })
+            .collect::<Vec<_>>();
         let faces: Vec<Rectangle> = faces.iter().map(|face| *face.bbox()).collect();
 
+        // 検出した顔の切り出しから特徴点を推定
+        let landmarks = faces
+            .iter()
+            .map(|face| {
+                estimate_landmarks(&grey_image_vec, self.image_size.0, self.image_size.1, face)
+            })
+            .collect::<Vec<_>>();
+
         // tracking
-        let (added_rois, removed_rois) = self.tracker.track(&faces);
+        let (added_rois, tracked_removed_rois) = self.tracker.track(&faces, &landmarks);
+        removed_rois.extend(tracked_removed_rois);
 
         let rois = self.tracker.rois().iter().collect::<Vec<&TrackerRoi>>();
+        let estimates = rois.iter().map(|roi| roi.estimate()).collect();
 
         Ok(VideoFaceInfo {
             rois,
+            estimates,
+            detections,
             added_rois,
             removed_rois,
             span_time: self.performance.now() - start_time,
         })
     }
 
-    /// トラッカーを再設定
-    pub fn initialize_tracker(&mut self, tracker_option: TrackerOptions) -> Result<(), AppError> {
+    /// 静止画から顔を検出する．矩形と各矩形に対応する特徴点を元の画像の座標で返す
+    pub fn detect_image(
+        &mut self,
+        image: &web_sys::HtmlImageElement,
+    ) -> Result<(Vec<Rectangle>, Vec<Option<FaceLandmarks>>), AppError> {
+        let (natural_width, natural_height) =
+            (image.natural_width() as f64, image.natural_height() as f64);
+        let scale = (STILL_IMAGE_MAX_SIDE / natural_width.max(natural_height)).min(1.0);
         let (image_width, image_height) = (
-            (self.image_size.0 as f64
-                * (tracker_option.image_over_video_scale as f64
-                    / self.tracker_option.image_over_video_scale)) as u32,
-            (self.image_size.1 as f64
-                * (tracker_option.image_over_video_scale as f64
-                    / self.tracker_option.image_over_video_scale)) as u32,
+            (natural_width * scale) as u32,
+            (natural_height * scale) as u32,
         );
 
-        // 画像作成用のキャンパスを再設定
-        let make_image_canvas = canvas(&self.make_image_context)?;
-        make_image_canvas.set_width(image_width);
-        make_image_canvas.set_height(image_height);
+        let image_context = context2d(&create_hidden_canvas(image_width, image_height)?)?;
+        image_context.draw_image_with_html_image_element_and_dw_and_dh(
+            image,
+            0.0,
+            0.0,
+            image_width as f64,
+            image_height as f64,
+        )?;
+
+        let image_vec = image_context
+            .get_image_data(0.0, 0.0, image_width as f64, image_height as f64)?
+            .data()
+            .0;
+
+        let grey_image_vec = convert_rgba_to_luma(image_vec);
+
+        let faces = detect_faces(
+            &mut *self.detector,
+            &grey_image_vec,
+            image_width,
+            image_height,
+        );
+
+        let landmarks = faces
+            .iter()
+            .map(|face| {
+                estimate_landmarks(&grey_image_vec, image_width, image_height, face.bbox()).map(
+                    |mut landmarks| {
+                        landmarks.rescale(1.0 / scale, 1.0 / scale);
+                        landmarks
+                    },
+                )
+            })
+            .collect();
+        let faces = faces
+            .iter()
+            .map(|face| {
+                let bbox = face.bbox();
+                Rectangle::new(
+                    (bbox.x() as f64 / scale) as i32,
+                    (bbox.y() as f64 / scale) as i32,
+                    (bbox.width() as f64 / scale) as u32,
+                    (bbox.height() as f64 / scale) as u32,
+                )
+            })
+            .collect();
+        Ok((faces, landmarks))
+    }
+
+    /// トラッカーを再設定
+    pub fn initialize_tracker(&mut self, tracker_option: TrackerOptions) -> Result<(), AppError> {
+        // オプションを更新して画像作成用のキャンパスとトラッカーを再設定
+        self.tracker_option = tracker_option.clone();
+        self.fit_to_video()?;
 
         // 検出器の再設定
         self.detector
@@ -235,8 +418,191 @@ impl VideoFaceTracker {
             tracker_option.slide_window_step,
         );
 
-        // トラッカーの初期化
-        self.tracker = Tracker::new(tracker_option.allowable_not_detect_count);
+        Ok(())
+    }
+
+    /// 検出されない間に覆う範囲を広げる方法
+    pub fn roi_growth(&self) -> RoiGrowthOptions {
+        self.tracker_option.roi_growth
+    }
+
+    /// 生存しているトラックの位置の履歴のCSV．座標はビデオの座標
+    pub fn trajectories_csv(&self) -> String {
+        let to_video = Transform::scale(
+            self.stream_video.video_width() as f64 / self.image_size.0 as f64,
+            self.stream_video.video_height() as f64 / self.image_size.1 as f64,
+        );
+        trajectories_csv(self.tracker.rois(), &to_video)
+    }
+
+    /// 入力のビデオ要素
+    pub fn stream_video(&self) -> &web_sys::HtmlVideoElement {
+        &self.stream_video
+    }
+
+    /// 検出器への入力画像のサイズ(width, height)
+    pub fn image_size(&self) -> (u32, u32) {
+        self.image_size
+    }
+
+    /// 現在のトラッキング結果(入力画像の座標)
+    pub fn rois(&self) -> Vec<&TrackerRoi> {
+        self.tracker.rois().iter().collect()
+    }
+
+    /// ビデオの入力ソース
+    pub fn source(&self) -> &VideoSource {
+        &self.source
+    }
+
+    /// ソースのストリームが終了したかどうか．画面共有をユーザーが停止した場合など
+    pub fn is_source_ended(&self) -> bool {
+        self.source_stream
+            .media_stream()
+            .map(is_stream_ended)
+            .unwrap_or(false)
+    }
+
+    /// ソースのストリーム(ファイルの場合はNone)
+    pub fn media_stream(&self) -> Option<&web_sys::MediaStream> {
+        self.source_stream.media_stream()
+    }
+
+    /// 合成映像の場合は真値の矩形(入力画像の座標)
+    pub fn ground_truth(&self) -> Option<Vec<Rect>> {
+        let scale = self.tracker_option.image_over_video_scale;
+        let video_to_image = Transform::scale(scale, scale);
+        self.source_stream.ground_truth().map(|faces| {
+            faces
+                .iter()
+                .map(|face| video_to_image.apply_rect(face))
+                .collect::<Vec<_>>()
+        })
+    }
+
+    /// ストリームのオプション
+    pub fn video_options(&self) -> &VideoOptions {
+        &self.video_options
+    }
+
+    /// ビデオの入力ソースを入れ替える．古いソースは解放する
+    pub fn replace_source(&mut self, source: VideoSource, source_stream: SourceStream) {
+        release_source(&self.source, &self.source_stream);
+        attach_source(&self.stream_video, &source, source_stream.media_stream());
+        self.source = source;
+        self.source_stream = source_stream;
+        self.lifecycle_state = LifecycleState::Running;
+        self.last_playback = None;
+    }
+
+    /// 一時停止中かどうか
+    pub fn is_paused(&self) -> bool {
+        self.lifecycle_state == LifecycleState::Paused
+    }
+
+    /// 再開時にストリームを取得し直す必要があるかどうか
+    pub fn needs_reopen(&self) -> bool {
+        self.is_paused() && self.source.reopens_on_resume()
+    }
+
+    /// 一時停止する．カメラのトラックは停止し，その他のストリームのトラックは無効化する
+    pub fn pause(&mut self) {
+        if self.lifecycle_state != LifecycleState::Running {
+            return;
+        }
+        if let Err(e) = self.stream_video.pause() {
+            log::error!("{e:?}");
+        }
+        if self.source.reopens_on_resume() {
+            release_source(&self.source, &self.source_stream);
+            self.source_stream = SourceStream::default();
+        } else {
+            self.source_stream.set_enabled(false);
+        }
+        self.lifecycle_state = LifecycleState::Paused;
+    }
+
+    /// 一時停止から再開する．ビデオの再生はplay_videoで行う
+    /// - source_stream: needs_reopenの場合に取得し直したストリーム
+    pub fn resume(&mut self, source_stream: Option<SourceStream>) {
+        if self.lifecycle_state != LifecycleState::Paused {
+            return;
+        }
+        match source_stream {
+            Some(source_stream) => {
+                attach_source(
+                    &self.stream_video,
+                    &self.source,
+                    source_stream.media_stream(),
+                );
+                self.source_stream = source_stream;
+            }
+            None => self.source_stream.set_enabled(true),
+        }
+        self.lifecycle_state = LifecycleState::Running;
+    }
+
+    /// 終了する．全てのトラックを停止してソースを解放する．終了後は再開できない
+    pub fn shutdown(&mut self) {
+        if self.lifecycle_state == LifecycleState::Shutdown {
+            return;
+        }
+        if let Err(e) = self.stream_video.pause() {
+            log::error!("{e:?}");
+        }
+        release_source(&self.source, &self.source_stream);
+        self.source_stream = SourceStream::default();
+        detach_source(&self.stream_video);
+        self.reset_rois.extend(self.tracker.reset());
+        self.lifecycle_state = LifecycleState::Shutdown;
+    }
+
+    /// ストリームのオプションを更新する．制約の適用はapply_video_constraintsで行う
+    pub fn set_video_options(&mut self, video_options: VideoOptions) {
+        self.video_options = video_options;
+    }
+
+    /// ビデオのサイズに合わせて画像作成用のキャンバスを再設定し，トラッカーを初期化
+    pub fn fit_to_video(&mut self) -> Result<(), AppError> {
+        let (image_width, image_height) = image_size_from_video(
+            &self.stream_video,
+            self.tracker_option.image_over_video_scale,
+        );
+
+        let make_image_canvas = canvas(&self.make_image_context)?;
+        make_image_canvas.set_width(image_width);
+        make_image_canvas.set_height(image_height);
+        self.image_size = (image_width, image_height);
+
+        // トラッカーの初期化．IDは作り直した後も再利用しない
+        self.reset_rois.extend(self.tracker.reset());
+        self.tracker.allowable_not_detect_count = self.tracker_option.allowable_not_detect_count;
+        self.tracker.history_length = self.tracker_option.trail_length;
+
+        Ok(())
+    }
+
+    /// ビデオのサイズの変更(画面の回転など)に画像作成用のキャンバスを合わせる．トラッキング結果はスケールして維持する
+    pub fn resize_to_video(&mut self) -> Result<(), AppError> {
+        let (image_width, image_height) = image_size_from_video(
+            &self.stream_video,
+            self.tracker_option.image_over_video_scale,
+        );
+        if (image_width, image_height) == self.image_size || image_width == 0 || image_height == 0 {
+            return Ok(());
+        }
+
+        let make_image_canvas = canvas(&self.make_image_context)?;
+        make_image_canvas.set_width(image_width);
+        make_image_canvas.set_height(image_height);
+
+        if self.image_size.0 > 0 && self.image_size.1 > 0 {
+            self.tracker.rescale(
+                image_width as f64 / self.image_size.0 as f64,
+                image_height as f64 / self.image_size.1 as f64,
+            );
+        }
+        self.image_size = (image_width, image_height);
 
         Ok(())
     }
@@ -244,8 +610,27 @@ impl VideoFaceTracker {
 
 impl Drop for VideoFaceTracker {
     fn drop(&mut self) {
+        self.shutdown(); // カメラのトラックを停止
         canvas(&self.make_image_context)
             .expect("Sanity Check")
             .remove(); // DOMツリーから削除
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seek_jump_compares_with_elapsed_time() {
+        // 通常の再生はインターバルが長くてもシークではない
+        assert!(!is_seek_jump(0.1, 0.1, 1.0));
+        assert!(!is_seek_jump(2.0, 2.0, 1.0));
+        assert!(!is_seek_jump(4.0, 2.0, 2.0));
+        // 読み込みの待ちや一時停止で遅れた場合もシークではない
+        assert!(!is_seek_jump(0.0, 5.0, 1.0));
+
+        assert!(is_seek_jump(10.0, 0.1, 1.0));
+        assert!(is_seek_jump(-0.5, 0.1, 1.0));
+    }
+}
@@ -0,0 +1,264 @@
+use super::super::dom_utils::window;
+use crate::error::AppError;
+
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+
+/// カメラの向き(facingMode)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FacingMode {
+    /// フロントカメラ
+    User,
+    /// バックカメラ
+    Environment,
+}
+
+impl FacingMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            FacingMode::User => "user",
+            FacingMode::Environment => "environment",
+        }
+    }
+}
+
+/// 利用するカメラの選択
+#[derive(Clone, Debug, PartialEq, Default)]
+pub enum CameraSelection {
+    /// ブラウザに任せる
+    #[default]
+    Default,
+    /// 向きを指定
+    Facing(FacingMode),
+    /// デバイスIDを指定
+    Device(String),
+}
+
+impl CameraSelection {
+    /// セレクトボックスの値に変換
+    pub fn to_value(&self) -> String {
+        match self {
+            CameraSelection::Default => "default".to_string(),
+            CameraSelection::Facing(facing_mode) => format!("facing:{}", facing_mode.as_str()),
+            CameraSelection::Device(device_id) => format!("device:{device_id}"),
+        }
+    }
+
+    /// セレクトボックスの値から変換
+    pub fn from_value(value: &str) -> Self {
+        if let Some(device_id) = value.strip_prefix("device:") {
+            CameraSelection::Device(device_id.to_string())
+        } else {
+            match value {
+                "facing:user" => CameraSelection::Facing(FacingMode::User),
+                "facing:environment" => CameraSelection::Facing(FacingMode::Environment),
+                _ => CameraSelection::Default,
+            }
+        }
+    }
+}
+
+/// 解像度やフレームレートの制約の範囲．Noneの場合は指定しない
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct ConstraintRange {
+    pub min: Option<f64>,
+    pub ideal: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl ConstraintRange {
+    fn is_empty(&self) -> bool {
+        self.min.is_none() && self.ideal.is_none() && self.max.is_none()
+    }
+
+    /// 整数値の制約(width, height)に変換
+    fn to_long_range(self) -> web_sys::ConstrainLongRange {
+        let mut range = web_sys::ConstrainLongRange::new();
+        if let Some(min) = self.min {
+            range.min(min as i32);
+        }
+        if let Some(ideal) = self.ideal {
+            range.ideal(ideal as i32);
+        }
+        if let Some(max) = self.max {
+            range.max(max as i32);
+        }
+        range
+    }
+
+    /// 実数値の制約(frameRate)に変換
+    fn to_double_range(self) -> web_sys::ConstrainDoubleRange {
+        let mut range = web_sys::ConstrainDoubleRange::new();
+        if let Some(min) = self.min {
+            range.min(min);
+        }
+        if let Some(ideal) = self.ideal {
+            range.ideal(ideal);
+        }
+        if let Some(max) = self.max {
+            range.max(max);
+        }
+        range
+    }
+}
+
+/// カメラのストリームのオプション
+#[derive(Clone, Debug, PartialEq)]
+pub struct VideoOptions {
+    /// 幅[px]
+    pub width: ConstraintRange,
+    /// 高さ[px]
+    pub height: ConstraintRange,
+    /// フレームレート[fps]
+    pub frame_rate: ConstraintRange,
+}
+
+impl Default for VideoOptions {
+    fn default() -> Self {
+        VideoOptions {
+            width: ConstraintRange {
+                ideal: Some(640.0),
+                max: Some(1920.0),
+                ..Default::default()
+            },
+            height: ConstraintRange {
+                ideal: Some(480.0),
+                max: Some(1080.0),
+                ..Default::default()
+            },
+            frame_rate: ConstraintRange {
+                ideal: Some(30.0),
+                max: Some(60.0),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// カメラの選択とオプションからビデオトラックの制約を作成
+fn video_constraints(
+    selection: &CameraSelection,
+    video_options: &VideoOptions,
+) -> web_sys::MediaTrackConstraints {
+    let mut constraints = web_sys::MediaTrackConstraints::new();
+
+    match selection {
+        CameraSelection::Default => {}
+        CameraSelection::Facing(facing_mode) => {
+            constraints.facing_mode(&JsValue::from_str(facing_mode.as_str()));
+        }
+        CameraSelection::Device(device_id) => {
+            let mut device_id_constraint = web_sys::ConstrainDomStringParameters::new();
+            device_id_constraint.exact(&JsValue::from_str(device_id));
+            constraints.device_id(&device_id_constraint);
+        }
+    }
+
+    if !video_options.width.is_empty() {
+        constraints.width(&video_options.width.to_long_range());
+    }
+    if !video_options.height.is_empty() {
+        constraints.height(&video_options.height.to_long_range());
+    }
+    if !video_options.frame_rate.is_empty() {
+        constraints.frame_rate(&video_options.frame_rate.to_double_range());
+    }
+
+    constraints
+}
+
+/// カメラデバイスの情報
+#[derive(Clone, Debug, PartialEq)]
+pub struct CameraDevice {
+    pub device_id: String,
+    pub label: String,
+}
+
+/// 利用可能なカメラを列挙する．ラベルはメディアの利用許可後でないと空になる
+pub async fn enumerate_cameras() -> Result<Vec<CameraDevice>, AppError> {
+    let devices = JsFuture::from(window().navigator().media_devices()?.enumerate_devices()?)
+        .await?
+        .dyn_into::<js_sys::Array>()
+        .map_err(|js_value| {
+            AppError::JsError(format!(
+                "Sanity Check. Cannot convert from: {js_value:?} into js_sys::Array"
+            ))
+        })?;
+
+    let cameras = devices
+        .iter()
+        .filter_map(|device| device.dyn_into::<web_sys::MediaDeviceInfo>().ok())
+        .filter(|device| device.kind() == web_sys::MediaDeviceKind::Videoinput)
+        .enumerate()
+        .map(|(i, device)| {
+            let label = device.label();
+            CameraDevice {
+                device_id: device.device_id(),
+                label: if label.is_empty() {
+                    format!("カメラ{}", i + 1)
+                } else {
+                    label
+                },
+            }
+        })
+        .collect();
+
+    Ok(cameras)
+}
+
+/// 選択したカメラのストリームを取得する
+pub async fn get_camera_stream(
+    selection: &CameraSelection,
+    video_options: &VideoOptions,
+) -> Result<web_sys::MediaStream, AppError> {
+    let mut media_constraints = web_sys::MediaStreamConstraints::new();
+    media_constraints.audio(&JsValue::FALSE);
+    media_constraints.video(&video_constraints(selection, video_options));
+
+    let stream_promise = window()
+        .navigator()
+        .media_devices()?
+        .get_user_media_with_constraints(&media_constraints)?;
+
+    JsFuture::from(stream_promise)
+        .await?
+        .dyn_into::<web_sys::MediaStream>()
+        .map_err(|js_value| {
+            AppError::JsError(format!(
+                "Sanity Check. Cannot convert from: {js_value:?} into web_sys::MediaStream"
+            ))
+        })
+}
+
+/// 再生中のストリームのビデオトラックに制約を適用する
+pub async fn apply_video_constraints(
+    stream: &web_sys::MediaStream,
+    selection: &CameraSelection,
+    video_options: &VideoOptions,
+) -> Result<(), AppError> {
+    let constraints = video_constraints(selection, video_options);
+    for track in stream.get_video_tracks().iter() {
+        if let Ok(track) = track.dyn_into::<web_sys::MediaStreamTrack>() {
+            JsFuture::from(track.apply_constraints_with_constraints(&constraints)?).await?;
+        }
+    }
+    Ok(())
+}
+
+/// ストリームの全トラックの有効・無効を切り替える．無効の間は黒いフレームになる
+pub fn set_stream_enabled(stream: &web_sys::MediaStream, enabled: bool) {
+    for track in stream.get_tracks().iter() {
+        if let Ok(track) = track.dyn_into::<web_sys::MediaStreamTrack>() {
+            track.set_enabled(enabled);
+        }
+    }
+}
+
+/// ストリームの全トラックを停止する(カメラの解放)
+pub fn stop_stream(stream: &web_sys::MediaStream) {
+    for track in stream.get_tracks().iter() {
+        if let Ok(track) = track.dyn_into::<web_sys::MediaStreamTrack>() {
+            track.stop();
+        }
+    }
+}
@@ -0,0 +1,337 @@
+use super::super::geometry::Rect;
+use rustface::Rectangle;
+
+/// 画像座標上の点
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LandmarkPoint {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// 顔の5点の特徴点(入力画像の座標)．左右は画像上の左右．
+/// 検出器(rustface)は矩形しか出力しないため，矩形内の輝度から推定した値であり検出器の出力ではない
+#[derive(Clone, Debug, PartialEq)]
+pub struct FaceLandmarks {
+    pub left_eye: LandmarkPoint,
+    pub right_eye: LandmarkPoint,
+    pub nose: LandmarkPoint,
+    pub left_mouth: LandmarkPoint,
+    pub right_mouth: LandmarkPoint,
+}
+
+impl LandmarkPoint {
+    /// 座標をスケールする
+    pub fn rescale(&mut self, scale_x: f64, scale_y: f64) {
+        self.x *= scale_x;
+        self.y *= scale_y;
+    }
+
+    /// 座標を平行移動する
+    pub fn translate(&mut self, dx: f64, dy: f64) {
+        self.x += dx;
+        self.y += dy;
+    }
+}
+
+impl FaceLandmarks {
+    /// 全ての特徴点の座標をスケールする
+    pub fn rescale(&mut self, scale_x: f64, scale_y: f64) {
+        for point in [
+            &mut self.left_eye,
+            &mut self.right_eye,
+            &mut self.nose,
+            &mut self.left_mouth,
+            &mut self.right_mouth,
+        ] {
+            point.rescale(scale_x, scale_y);
+        }
+    }
+
+    /// 全ての特徴点の座標を平行移動する
+    pub fn translate(&mut self, dx: f64, dy: f64) {
+        for point in [
+            &mut self.left_eye,
+            &mut self.right_eye,
+            &mut self.nose,
+            &mut self.left_mouth,
+            &mut self.right_mouth,
+        ] {
+            point.translate(dx, dy);
+        }
+    }
+
+    /// 両目を結ぶ直線から求めた画像面内の回転角(roll)[rad]．時計回りが正．
+    /// 目の位置が推定値のため，回転角も目安として扱う
+    pub fn roll_angle(&self) -> f64 {
+        (self.right_eye.y - self.left_eye.y).atan2(self.right_eye.x - self.left_eye.x)
+    }
+}
+
+/// 切り出した顔画像の一部の領域(顔の幅・高さに対する比率で指定)
+struct SearchRegion {
+    left: f64,
+    top: f64,
+    right: f64,
+    bottom: f64,
+}
+
+/// 輝度画像の顔領域の切り出し
+struct FaceCrop<'a> {
+    luma: &'a [u8],
+    image_width: usize,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl<'a> FaceCrop<'a> {
+    /// 顔の矩形を画像内に収めて切り出す．小さすぎる場合はNone
+    fn new(luma: &'a [u8], image_width: u32, image_height: u32, face: &Rectangle) -> Option<Self> {
+        let bounds = Rect::new(0.0, 0.0, image_width as f64, image_height as f64);
+        let crop = Rect::from(face).clamp_to(&bounds)?;
+        let (x_start, y_start) = (crop.x as usize, crop.y as usize);
+        let (x_end, y_end) = (crop.right() as usize, crop.bottom() as usize);
+
+        if x_end.saturating_sub(x_start) < 8 || y_end.saturating_sub(y_start) < 8 {
+            return None;
+        }
+
+        Some(Self {
+            luma,
+            image_width: image_width as usize,
+            x: x_start,
+            y: y_start,
+            width: x_end - x_start,
+            height: y_end - y_start,
+        })
+    }
+
+    /// 比率で指定した領域を切り出し内のピクセル範囲(x_range, y_range)に変換
+    fn region_bounds(&self, region: &SearchRegion) -> ((usize, usize), (usize, usize)) {
+        let to_range = |start: f64, end: f64, size: usize| {
+            let start = ((start * size as f64) as usize).min(size - 1);
+            let end = ((end * size as f64) as usize).clamp(start + 1, size);
+            (start, end)
+        };
+        (
+            to_range(region.left, region.right, self.width),
+            to_range(region.top, region.bottom, self.height),
+        )
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> u8 {
+        self.luma[(self.y + y) * self.image_width + (self.x + x)]
+    }
+
+    /// 領域内の暗い画素の重心(目の位置の推定に利用)
+    fn dark_centroid(&self, region: &SearchRegion) -> LandmarkPoint {
+        let ((x_start, x_end), (y_start, y_end)) = self.region_bounds(region);
+
+        let (mut min_value, mut sum, mut count) = (u8::MAX, 0_u64, 0_u64);
+        for y in y_start..y_end {
+            for x in x_start..x_end {
+                let value = self.pixel(x, y);
+                min_value = min_value.min(value);
+                sum += value as u64;
+                count += 1;
+            }
+        }
+        let mean = sum as f64 / count as f64;
+        // 最小値から平均までの下位3割を暗い画素とする
+        let thresh = min_value as f64 + (mean - min_value as f64) * 0.3;
+
+        let (mut sum_x, mut sum_y, mut dark_count) = (0.0, 0.0, 0.0);
+        for y in y_start..y_end {
+            for x in x_start..x_end {
+                if (self.pixel(x, y) as f64) <= thresh {
+                    sum_x += x as f64;
+                    sum_y += y as f64;
+                    dark_count += 1.0;
+                }
+            }
+        }
+
+        LandmarkPoint {
+            x: (self.x as f64) + sum_x / dark_count,
+            y: (self.y as f64) + sum_y / dark_count,
+        }
+    }
+
+    /// 領域内で平均輝度が最も小さい行(口の位置の推定に利用)
+    fn darkest_row(&self, region: &SearchRegion) -> f64 {
+        let ((x_start, x_end), (y_start, y_end)) = self.region_bounds(region);
+
+        let darkest_y = (y_start..y_end)
+            .map(|y| {
+                let row_sum: u64 = (x_start..x_end).map(|x| self.pixel(x, y) as u64).sum();
+                (y, row_sum)
+            })
+            .min_by_key(|(_, row_sum)| *row_sum)
+            .map(|(y, _)| y)
+            .unwrap_or(y_start);
+
+        (self.y + darkest_y) as f64
+    }
+}
+
+/// 検出した顔の矩形の輝度画像から5点の特徴点をヒューリスティックに推定する(検出器の出力ではない)．
+/// 目は上半分の左右の暗い領域，口は下部の最も暗い行から求め，鼻と口角は目の位置に対する幾何的な比率で決める．
+/// 眼鏡や影などで暗い領域がずれると特徴点もずれる
+pub fn estimate_landmarks(
+    luma: &[u8],
+    image_width: u32,
+    image_height: u32,
+    face: &Rectangle,
+) -> Option<FaceLandmarks> {
+    let crop = FaceCrop::new(luma, image_width, image_height, face)?;
+
+    let left_eye = crop.dark_centroid(&SearchRegion {
+        left: 0.1,
+        top: 0.2,
+        right: 0.5,
+        bottom: 0.55,
+    });
+    let right_eye = crop.dark_centroid(&SearchRegion {
+        left: 0.5,
+        top: 0.2,
+        right: 0.9,
+        bottom: 0.55,
+    });
+
+    // 両目の中点と目を結ぶ方向
+    let eye_center = LandmarkPoint {
+        x: (left_eye.x + right_eye.x) / 2.0,
+        y: (left_eye.y + right_eye.y) / 2.0,
+    };
+    let (eye_dx, eye_dy) = (right_eye.x - left_eye.x, right_eye.y - left_eye.y);
+    let eye_distance = eye_dx.hypot(eye_dy);
+    if eye_distance < 1.0 {
+        return None;
+    }
+    let (unit_x, unit_y) = (eye_dx / eye_distance, eye_dy / eye_distance);
+    // 顔の下方向(目の方向に垂直)
+    let (down_x, down_y) = (-unit_y, unit_x);
+
+    let mouth_y = crop.darkest_row(&SearchRegion {
+        left: 0.25,
+        top: 0.65,
+        right: 0.75,
+        bottom: 0.95,
+    });
+    let mouth_distance = if down_y > 0.3 {
+        ((mouth_y - eye_center.y) / down_y).max(eye_distance * 0.5)
+    } else {
+        eye_distance // 大きく傾いている場合は目の間隔から決める
+    };
+
+    let mouth_center = LandmarkPoint {
+        x: eye_center.x + down_x * mouth_distance,
+        y: eye_center.y + down_y * mouth_distance,
+    };
+    let nose = LandmarkPoint {
+        x: eye_center.x + down_x * mouth_distance * 0.6,
+        y: eye_center.y + down_y * mouth_distance * 0.6,
+    };
+    let half_mouth_width = eye_distance * 0.4;
+
+    Some(FaceLandmarks {
+        left_eye,
+        right_eye,
+        nose,
+        left_mouth: LandmarkPoint {
+            x: mouth_center.x - unit_x * half_mouth_width,
+            y: mouth_center.y - unit_y * half_mouth_width,
+        },
+        right_mouth: LandmarkPoint {
+            x: mouth_center.x + unit_x * half_mouth_width,
+            y: mouth_center.y + unit_y * half_mouth_width,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IMAGE_SIZE: usize = 100;
+
+    /// 明るい背景に暗い目(5x5の正方形)と口(横線)を描いた輝度画像
+    fn face_image(left_eye: (usize, usize), right_eye: (usize, usize), mouth_y: usize) -> Vec<u8> {
+        let mut luma = vec![200_u8; IMAGE_SIZE * IMAGE_SIZE];
+        for (eye_x, eye_y) in [left_eye, right_eye] {
+            for y in (eye_y - 2)..=(eye_y + 2) {
+                for x in (eye_x - 2)..=(eye_x + 2) {
+                    luma[y * IMAGE_SIZE + x] = 0;
+                }
+            }
+        }
+        for x in 40..60 {
+            luma[mouth_y * IMAGE_SIZE + x] = 50;
+        }
+        luma
+    }
+
+    fn face() -> Rectangle {
+        Rectangle::new(10, 10, 80, 80)
+    }
+
+    fn estimate(luma: &[u8]) -> FaceLandmarks {
+        estimate_landmarks(luma, IMAGE_SIZE as u32, IMAGE_SIZE as u32, &face()).unwrap()
+    }
+
+    fn is_inside_face(point: &LandmarkPoint) -> bool {
+        Rect::from(&face()).contains(point.x, point.y)
+    }
+
+    #[test]
+    fn level_face_has_no_roll() {
+        let landmarks = estimate(&face_image((32, 40), (68, 40), 75));
+        assert_eq!(landmarks.left_eye, LandmarkPoint { x: 32.0, y: 40.0 });
+        assert_eq!(landmarks.right_eye, LandmarkPoint { x: 68.0, y: 40.0 });
+        assert!(landmarks.roll_angle().abs() < 1e-9);
+
+        // 鼻は両目の中点の真下，口角は口の行に並ぶ
+        assert!((landmarks.nose.x - 50.0).abs() < 1e-9);
+        assert!(landmarks.nose.y > 40.0 && landmarks.nose.y < 75.0);
+        assert!((landmarks.left_mouth.y - 75.0).abs() < 1e-9);
+        assert!((landmarks.right_mouth.y - 75.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn tilted_face_has_roll_of_eye_line() {
+        let landmarks = estimate(&face_image((32, 34), (68, 46), 75));
+        let expected = 12.0_f64.atan2(36.0);
+        assert!((landmarks.roll_angle() - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn landmarks_stay_inside_face() {
+        for (left_eye, right_eye) in [
+            ((32, 40), (68, 40)),
+            ((32, 34), (68, 46)),
+            ((32, 46), (68, 34)),
+        ] {
+            let landmarks = estimate(&face_image(left_eye, right_eye, 75));
+            for point in [
+                &landmarks.left_eye,
+                &landmarks.right_eye,
+                &landmarks.nose,
+                &landmarks.left_mouth,
+                &landmarks.right_mouth,
+            ] {
+                assert!(is_inside_face(point), "{point:?} is outside of the face");
+            }
+        }
+    }
+
+    #[test]
+    fn too_small_face_is_skipped() {
+        let luma = face_image((32, 40), (68, 40), 75);
+        let small_face = Rectangle::new(10, 10, 6, 6);
+        assert_eq!(
+            estimate_landmarks(&luma, IMAGE_SIZE as u32, IMAGE_SIZE as u32, &small_face),
+            None
+        );
+    }
+}
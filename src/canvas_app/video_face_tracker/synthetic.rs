@@ -0,0 +1,276 @@
+use super::super::dom_utils::{context2d, create_hidden_canvas, window};
+use super::super::geometry::Rect;
+use super::tracker_roi::TrackerRoi;
+use crate::error::AppError;
+use crate::Interval;
+
+use std::cell::Cell;
+use std::f64::consts::PI;
+use std::rc::Rc;
+use wasm_bindgen::JsValue;
+
+/// 合成映像の幅[px]
+const SYNTHETIC_WIDTH: u32 = 640;
+/// 合成映像の高さ[px]
+const SYNTHETIC_HEIGHT: u32 = 480;
+/// 合成映像の描画間隔[ms]
+const SYNTHETIC_FRAME_SPAN: u32 = 33;
+/// 合成映像の顔の数
+const SYNTHETIC_FACE_COUNT: usize = 2;
+
+/// 時刻time[s]における各顔の真値の矩形(ビデオの座標)．各顔はリサージュ曲線上を動く
+pub fn ground_truth_faces(time: f64) -> Vec<Rect> {
+    let (width, height) = (SYNTHETIC_WIDTH as f64, SYNTHETIC_HEIGHT as f64);
+
+    (0..SYNTHETIC_FACE_COUNT)
+        .map(|i| {
+            let phase = i as f64 * PI;
+            let size = 130.0 * (1.0 + 0.15 * (0.5 * time + phase).sin());
+            let center_x = width / 2.0 + width * 0.28 * (0.4 * time + phase).sin();
+            let center_y = height / 2.0 + height * 0.18 * (0.7 * time + phase / 2.0).sin();
+            Rect::new(center_x - size / 2.0, center_y - size / 2.0, size, size)
+        })
+        .collect()
+}
+
+/// 真値の矩形に合わせて顔を描画する
+fn draw_face(context: &web_sys::CanvasRenderingContext2d, face: &Rect) -> Result<(), JsValue> {
+    let (center_x, center_y, size) = (face.center_x(), face.center_y(), face.width);
+
+    // 髪
+    context.set_fill_style(&JsValue::from_str("#2b1d14"));
+    context.begin_path();
+    context.ellipse(
+        center_x,
+        center_y - size * 0.12,
+        size * 0.62,
+        size * 0.78,
+        0.0,
+        0.0,
+        2.0 * PI,
+    )?;
+    context.fill();
+
+    // 顔の輪郭
+    context.set_fill_style(&JsValue::from_str("#e8b995"));
+    context.begin_path();
+    context.ellipse(
+        center_x,
+        center_y + size * 0.05,
+        size * 0.52,
+        size * 0.68,
+        0.0,
+        0.0,
+        2.0 * PI,
+    )?;
+    context.fill();
+
+    // 眉と目
+    for side in [-1.0, 1.0] {
+        let eye_x = center_x + side * size * 0.2;
+        let eye_y = center_y - size * 0.08;
+
+        context.set_fill_style(&JsValue::from_str("#3a2618"));
+        context.fill_rect(
+            eye_x - size * 0.11,
+            eye_y - size * 0.12,
+            size * 0.22,
+            size * 0.035,
+        );
+
+        context.set_fill_style(&JsValue::from_str("#ffffff"));
+        context.begin_path();
+        context.ellipse(eye_x, eye_y, size * 0.09, size * 0.045, 0.0, 0.0, 2.0 * PI)?;
+        context.fill();
+
+        context.set_fill_style(&JsValue::from_str("#1b1b1b"));
+        context.begin_path();
+        context.arc(eye_x, eye_y, size * 0.04, 0.0, 2.0 * PI)?;
+        context.fill();
+    }
+
+    // 鼻の影
+    context.set_fill_style(&JsValue::from_str("#c98f6b"));
+    context.begin_path();
+    context.move_to(center_x, center_y - size * 0.02);
+    context.line_to(center_x - size * 0.07, center_y + size * 0.17);
+    context.line_to(center_x + size * 0.07, center_y + size * 0.17);
+    context.close_path();
+    context.fill();
+
+    // 口
+    context.set_fill_style(&JsValue::from_str("#8c3b3b"));
+    context.begin_path();
+    context.ellipse(
+        center_x,
+        center_y + size * 0.3,
+        size * 0.16,
+        size * 0.05,
+        0.0,
+        0.0,
+        2.0 * PI,
+    )?;
+    context.fill();
+
+    Ok(())
+}
+
+/// 背景と全ての顔を描画する
+fn draw_frame(context: &web_sys::CanvasRenderingContext2d, time: f64) -> Result<(), JsValue> {
+    let (width, height) = (SYNTHETIC_WIDTH as f64, SYNTHETIC_HEIGHT as f64);
+
+    let background = context.create_linear_gradient(0.0, 0.0, 0.0, height);
+    background.add_color_stop(0.0, "#9fb4c7")?;
+    background.add_color_stop(1.0, "#5d6d7e")?;
+    context.set_fill_style(&background);
+    context.fill_rect(0.0, 0.0, width, height);
+
+    for face in ground_truth_faces(time) {
+        draw_face(context, &face)?;
+    }
+    Ok(())
+}
+
+/// 真値の既知な顔が動く合成映像．キャンバスのcaptureStreamでストリームを作成する
+pub struct SyntheticVideo {
+    /// 描画先のキャンバス
+    canvas: web_sys::HtmlCanvasElement,
+    /// 最後に描画したフレームの時刻[s]
+    frame_time: Rc<Cell<f64>>,
+    /// 描画のインターバル
+    _interval: Interval,
+}
+
+impl SyntheticVideo {
+    pub fn new() -> Result<Self, AppError> {
+        let canvas = create_hidden_canvas(SYNTHETIC_WIDTH, SYNTHETIC_HEIGHT)?;
+        let context = context2d(&canvas)?;
+        let performance = window()
+            .performance()
+            .ok_or(AppError::OtherError("Cannot get Performance.".to_string()))?;
+
+        let start_time = performance.now();
+        let frame_time = Rc::new(Cell::new(0.0));
+        draw_frame(&context, 0.0)?;
+
+        let interval = Interval::new(SYNTHETIC_FRAME_SPAN, {
+            let frame_time = frame_time.clone();
+            move || {
+                let time = (performance.now() - start_time) / 1000.0;
+                match draw_frame(&context, time) {
+                    Ok(_) => frame_time.set(time),
+                    Err(e) => log::error!("{e:?}"),
+                }
+            }
+        });
+
+        Ok(Self {
+            canvas,
+            frame_time,
+            _interval: interval,
+        })
+    }
+
+    /// 合成映像のストリーム
+    pub fn capture_stream(&self) -> Result<web_sys::MediaStream, AppError> {
+        Ok(self.canvas.capture_stream()?)
+    }
+
+    /// 最後に描画したフレームの真値の矩形(ビデオの座標)
+    pub fn ground_truth(&self) -> Vec<Rect> {
+        ground_truth_faces(self.frame_time.get())
+    }
+}
+
+/// 真値に対するトラッキングの確認結果
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrackingCheck {
+    /// 真値の顔の数
+    pub ground_truth_count: usize,
+    /// 中心が真値の矩形の内側にあるroiが存在する真値の顔の数
+    pub matched_count: usize,
+    /// 一致した顔の中心の誤差の平均[px]
+    pub mean_center_error: f64,
+}
+
+/// 真値の矩形とトラッキング結果(同じ座標)を比較する
+pub fn check_tracking(ground_truth: &[Rect], rois: &[&TrackerRoi]) -> TrackingCheck {
+    let errors = ground_truth
+        .iter()
+        .filter_map(|face| {
+            rois.iter()
+                .map(|roi| {
+                    (roi.center_x() - face.center_x()).hypot(roi.center_y() - face.center_y())
+                })
+                .filter(|error| *error < face.width / 2.0)
+                .min_by(|x, y| x.total_cmp(y))
+        })
+        .collect::<Vec<f64>>();
+
+    TrackingCheck {
+        ground_truth_count: ground_truth.len(),
+        matched_count: errors.len(),
+        mean_center_error: if errors.is_empty() {
+            0.0
+        } else {
+            errors.iter().sum::<f64>() / errors.len() as f64
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ground_truth_faces_stay_in_frame() {
+        for step in 0..200 {
+            let faces = ground_truth_faces(step as f64 * 0.1);
+            assert_eq!(faces.len(), SYNTHETIC_FACE_COUNT);
+            for face in faces {
+                assert!(face.x >= 0.0 && face.right() <= SYNTHETIC_WIDTH as f64);
+                assert!(face.y >= 0.0 && face.bottom() <= SYNTHETIC_HEIGHT as f64);
+            }
+        }
+    }
+
+    #[test]
+    fn ground_truth_faces_are_deterministic_and_move() {
+        assert_eq!(ground_truth_faces(1.5), ground_truth_faces(1.5));
+
+        // 位相をずらしているため各顔は別の位置にあり，時間とともに動く
+        let faces = ground_truth_faces(0.0);
+        assert_ne!(faces[0], faces[1]);
+        let later = ground_truth_faces(1.0);
+        for (face, later_face) in faces.iter().zip(later.iter()) {
+            let moved = (later_face.center_x() - face.center_x())
+                .hypot(later_face.center_y() - face.center_y());
+            assert!(moved > 10.0, "{face:?} -> {later_face:?}");
+        }
+    }
+
+    #[test]
+    fn check_tracking_matches_nearest_roi() {
+        let ground_truth = [
+            Rect::new(0.0, 0.0, 100.0, 100.0),
+            Rect::new(300.0, 0.0, 100.0, 100.0),
+        ];
+        // 1つ目の顔には中心から(3, 4)ずれたroiと遠いroi，2つ目の顔には半径の外のroiしかない
+        let near = TrackerRoi::new(0, 3.0, 4.0, 100.0, 100.0);
+        let far = TrackerRoi::new(1, 30.0, 0.0, 100.0, 100.0);
+        let outside = TrackerRoi::new(2, 360.0, 0.0, 100.0, 100.0);
+        let check = check_tracking(&ground_truth, &[&far, &near, &outside]);
+        assert_eq!(
+            check,
+            TrackingCheck {
+                ground_truth_count: 2,
+                matched_count: 1,
+                mean_center_error: 5.0,
+            }
+        );
+
+        let empty = check_tracking(&ground_truth, &[]);
+        assert_eq!(empty.matched_count, 0);
+        assert_eq!(empty.mean_center_error, 0.0);
+    }
+}
@@ -1,3 +1,4 @@
+use super::landmark::FaceLandmarks;
 use super::tracker_roi::{RoiState, TrackerRoi};
 use rustface::Rectangle;
 
@@ -8,30 +9,45 @@ pub struct Tracker {
     pub rois: Vec<TrackerRoi>,
     id_counter: u32,
     pub allowable_not_detect_count: u32,
+    /// 各Roiが保持する位置の履歴の数
+    pub history_length: usize,
+    /// trackを呼んだ回数(位置の履歴の更新の番号)
+    update_count: u64,
 }
 
 impl Tracker {
     /// コンストラクタ
-    pub fn new(allowable_not_detect_count: u32) -> Self {
+    pub fn new(allowable_not_detect_count: u32, history_length: usize) -> Self {
         Self {
             rois: Vec::new(),
             id_counter: 0,
             allowable_not_detect_count,
+            history_length,
+            update_count: 0,
         }
     }
     /// 状態を一つ遷移させてトラッキング
+    /// - faces: 検出された矩形
+    /// - landmarks: 各矩形に対応する特徴点
+    ///
     /// Results
     /// - 追加されたRoiの配列
     /// - 削除されたRoiの配列
-    pub fn track(&mut self, faces: &Vec<Rectangle>) -> (Vec<TrackerRoi>, Vec<TrackerRoi>) {
+    pub fn track(
+        &mut self,
+        faces: &Vec<Rectangle>,
+        landmarks: &[Option<FaceLandmarks>],
+    ) -> (Vec<TrackerRoi>, Vec<TrackerRoi>) {
+        self.update_count += 1;
         let mut face_roi_connected_flags: Vec<bool> = vec![false; faces.len()];
         // roisとfacesの結び付け(割り当て問題として解かずにidの小さいものから最も使いものと結びつける(貪欲法))
         for roi in self.rois.iter_mut() {
             let min_dist_face_and_flag_opt = faces
                 .iter()
+                .zip(landmarks.iter())
                 .zip(face_roi_connected_flags.iter_mut())
                 .filter(|(_, flag)| !**flag) // まだマッチングしていないもののみ
-                .min_by(|(face_x, _), (face_y, _)| {
+                .min_by(|((face_x, _), _), ((face_y, _), _)| {
                     match roi
                         .distance_with_rect(*face_x)
                         .partial_cmp(&roi.distance_with_rect(*face_y))
@@ -43,13 +59,20 @@ impl Tracker {
                     }
                 });
 
-            if let Some((min_dist_face, flag)) = min_dist_face_and_flag_opt {
+            if let Some(((min_dist_face, face_landmarks), flag)) = min_dist_face_and_flag_opt {
                 // マッチングした場合
                 *flag = true;
+                // 前回の検出からの更新の回数で割って速度を求める
+                let updates = (roi.missed_updates() + 1) as f64;
+                let (last_center_x, last_center_y) = (roi.center_x(), roi.center_y());
                 roi.tl_x = min_dist_face.x() as f64;
                 roi.tl_y = min_dist_face.y() as f64;
                 roi.width = min_dist_face.width() as f64;
                 roi.height = min_dist_face.height() as f64;
+                roi.landmarks = face_landmarks.clone();
+                roi.velocity_x = (roi.center_x() - last_center_x) / updates;
+                roi.velocity_y = (roi.center_y() - last_center_y) / updates;
+                roi.record(self.update_count, self.history_length);
 
                 roi.detected();
             } else {
@@ -85,20 +108,23 @@ impl Tracker {
         //roiの追加
         let mut added_rois = Vec::<TrackerRoi>::new();
 
-        for (new_face, _) in faces
+        for ((new_face, face_landmarks), _) in faces
             .iter()
+            .zip(landmarks.iter())
             .zip(face_roi_connected_flags.iter())
             .filter(|(_, flag)| !**flag)
         // マッチングしていないみののみ
         {
             self.id_counter += 1;
-            let roi = TrackerRoi::new(
+            let mut roi = TrackerRoi::new(
                 self.id_counter,
                 new_face.x() as f64,
                 new_face.y() as f64,
                 new_face.width() as f64,
                 new_face.height() as f64,
             );
+            roi.landmarks = face_landmarks.clone();
+            roi.record(self.update_count, self.history_length);
 
             added_rois.push(roi.clone());
             self.rois.push(roi);
@@ -107,7 +133,39 @@ impl Tracker {
         (added_rois, removed_rois)
     }
 
+    /// 全てのRoiを削除して初期化する．IDのカウンタは戻さない(作り直した後もIDを再利用しない)
+    /// Results
+    /// - 削除されたRoiの配列
+    pub fn reset(&mut self) -> Vec<TrackerRoi> {
+        std::mem::take(&mut self.rois)
+    }
+
+    /// 全てのRoiを削除せずにスケールする
+    pub fn rescale(&mut self, scale_x: f64, scale_y: f64) {
+        for roi in self.rois.iter_mut() {
+            roi.rescale(scale_x, scale_y);
+        }
+    }
+
     pub fn rois(&self) -> &[TrackerRoi] {
         &self.rois
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_are_not_reused_after_reset() {
+        let mut tracker = Tracker::new(0, 4);
+        let (added_rois, _) = tracker.track(&vec![Rectangle::new(0, 0, 10, 10)], &[None]);
+        assert_eq!(added_rois[0].id, 1);
+
+        assert_eq!(tracker.reset().len(), 1);
+        assert!(tracker.rois().is_empty());
+
+        let (added_rois, _) = tracker.track(&vec![Rectangle::new(0, 0, 10, 10)], &[None]);
+        assert_eq!(added_rois[0].id, 2);
+    }
+}
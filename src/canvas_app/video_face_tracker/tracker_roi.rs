@@ -1,11 +1,77 @@
+use super::super::geometry::{Rect, Transform};
+use super::landmark::FaceLandmarks;
 use rustface::Rectangle;
 
+use std::collections::VecDeque;
+use std::fmt::Write;
+
+/// 検出されない間に覆う範囲を広げる方法
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum RoiGrowthMode {
+    /// 広げない(最後に検出された矩形のまま)
+    Off,
+    /// 観測した中心の速さで，見失ってから動ける範囲まで広げる
+    #[default]
+    Velocity,
+    /// 見失ってからの更新ごとに一定の割合で広げる
+    Rate,
+}
+
+impl RoiGrowthMode {
+    /// セレクトボックスの値に変換
+    pub fn to_value(self) -> &'static str {
+        match self {
+            RoiGrowthMode::Off => "off",
+            RoiGrowthMode::Velocity => "velocity",
+            RoiGrowthMode::Rate => "rate",
+        }
+    }
+
+    /// セレクトボックスの値から変換
+    pub fn from_value(value: &str) -> Self {
+        match value {
+            "off" => RoiGrowthMode::Off,
+            "rate" => RoiGrowthMode::Rate,
+            _ => RoiGrowthMode::Velocity,
+        }
+    }
+}
+
+/// 検出されない間に覆う範囲を広げるオプション
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RoiGrowthOptions {
+    pub mode: RoiGrowthMode,
+    /// 更新1回あたりに広げる割合(矩形の幅・高さに対する比率．Rateの場合)
+    pub rate: f64,
+    /// 広げた矩形の大きさの上限(元の矩形に対する倍率)
+    pub max_ratio: f64,
+}
+
+impl Default for RoiGrowthOptions {
+    fn default() -> Self {
+        Self {
+            mode: Default::default(),
+            rate: 0.15,
+            max_ratio: 2.0,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub enum RoiState {
     DETECTED,
     NOTDETECTED,
 }
 
+/// 位置の履歴の1点
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrackPoint {
+    /// トラッカーの更新の番号
+    pub update: u64,
+    /// 検出された矩形(入力画像の座標)
+    pub rect: Rect,
+}
+
 #[derive(Clone, Debug)]
 pub struct TrackerRoi {
     pub id: u32,
@@ -15,6 +81,14 @@ pub struct TrackerRoi {
     pub height: f64,
     pub state: RoiState,
     pub not_detected_count: u32,
+    /// 最後に検出されたときの特徴点
+    pub landmarks: Option<FaceLandmarks>,
+    /// 検出された中心の速度[px/更新](x方向)
+    pub velocity_x: f64,
+    /// 検出された中心の速度[px/更新](y方向)
+    pub velocity_y: f64,
+    /// 検出された位置の履歴．古いものから順に並び，上限を超えると古いものから捨てる
+    pub history: VecDeque<TrackPoint>,
 }
 
 impl TrackerRoi {
@@ -27,6 +101,101 @@ impl TrackerRoi {
             height,
             state: RoiState::DETECTED,
             not_detected_count: 0,
+            landmarks: None,
+            velocity_x: 0.0,
+            velocity_y: 0.0,
+            history: VecDeque::new(),
+        }
+    }
+
+    /// 現在の矩形を位置の履歴に追加する．max_lenを超えた分は古いものから捨てる
+    pub fn record(&mut self, update: u64, max_len: usize) {
+        self.history.push_back(TrackPoint {
+            update,
+            rect: self.rect(),
+        });
+        while self.history.len() > max_len {
+            self.history.pop_front();
+        }
+    }
+
+    /// 入力画像のサイズの変更に合わせて座標をスケールする
+    pub fn rescale(&mut self, scale_x: f64, scale_y: f64) {
+        self.tl_x *= scale_x;
+        self.tl_y *= scale_y;
+        self.width *= scale_x;
+        self.height *= scale_y;
+        self.velocity_x *= scale_x;
+        self.velocity_y *= scale_y;
+        for point in self.history.iter_mut() {
+            point.rect = Transform::scale(scale_x, scale_y).apply_rect(&point.rect);
+        }
+        if let Some(landmarks) = self.landmarks.as_mut() {
+            landmarks.rescale(scale_x, scale_y);
+        }
+    }
+
+    /// 特徴点と回転角
+    pub fn estimate(&self) -> FaceEstimate {
+        FaceEstimate {
+            id: self.id,
+            landmarks: self.landmarks.clone(),
+            roll_angle: self.roll_angle(),
+        }
+    }
+
+    /// 描画に使う値の写し．位置の履歴は含めない
+    /// - estimate: このroiの特徴点と回転角(VideoFaceInfo::estimates)
+    pub fn snapshot(&self, growth: &RoiGrowthOptions, estimate: FaceEstimate) -> RoiSnapshot {
+        debug_assert_eq!(self.id, estimate.id);
+        RoiSnapshot {
+            id: self.id,
+            rect: self.rect(),
+            cover_rect: self.cover_rect(growth),
+            state: self.state,
+            missed_updates: self.missed_updates(),
+            landmarks: estimate.landmarks,
+            roll_angle: estimate.roll_angle,
+        }
+    }
+
+    /// 矩形(入力画像の座標)
+    pub fn rect(&self) -> Rect {
+        Rect::new(self.tl_x, self.tl_y, self.width, self.height)
+    }
+
+    /// 匿名化で覆う矩形(入力画像の座標)．見失っている間は顔が動いた可能性のある範囲まで中心から広げる
+    pub fn cover_rect(&self, growth: &RoiGrowthOptions) -> Rect {
+        let rect = self.rect();
+        let missed_updates = self.missed_updates() as f64;
+        let (grow_x, grow_y) = match growth.mode {
+            RoiGrowthMode::Off => (0.0, 0.0),
+            RoiGrowthMode::Velocity => {
+                // 向きが変わる場合もあるため全ての方向に広げる
+                let distance = self.velocity_x.hypot(self.velocity_y) * missed_updates;
+                (distance, distance)
+            }
+            RoiGrowthMode::Rate => (
+                rect.width * growth.rate * missed_updates / 2.0,
+                rect.height * growth.rate * missed_updates / 2.0,
+            ),
+        };
+        let max_growth = growth.max_ratio.max(1.0) - 1.0;
+        let grow_x = grow_x.min(rect.width * max_growth / 2.0);
+        let grow_y = grow_y.min(rect.height * max_growth / 2.0);
+        Rect::new(
+            rect.x - grow_x,
+            rect.y - grow_y,
+            rect.width + grow_x * 2.0,
+            rect.height + grow_y * 2.0,
+        )
+    }
+
+    /// 見失ってからの更新の回数．検出されている場合は0
+    pub fn missed_updates(&self) -> u32 {
+        match self.state {
+            RoiState::DETECTED => 0,
+            RoiState::NOTDETECTED => self.not_detected_count + 1,
         }
     }
 
@@ -36,6 +205,14 @@ impl TrackerRoi {
     pub fn center_y(&self) -> f64 {
         self.tl_y + self.height / 2.0
     }
+    /// 今回の更新で検出されたかどうか
+    pub fn is_detected(&self) -> bool {
+        matches!(self.state, RoiState::DETECTED)
+    }
+    /// 特徴点から求めた画像面内の回転角[rad]
+    pub fn roll_angle(&self) -> Option<f64> {
+        self.landmarks.as_ref().map(FaceLandmarks::roll_angle)
+    }
     pub fn distance_with_rect(&self, rect: &Rectangle) -> f64 {
         let rect_center_x = (rect.x() + rect.width() as i32 / 2) as f64;
         let rect_center_y = (rect.y() + rect.height() as i32 / 2) as f64;
@@ -63,3 +240,148 @@ impl TrackerRoi {
         }
     }
 }
+
+/// トラックの特徴点と回転角
+#[derive(Clone, Debug)]
+pub struct FaceEstimate {
+    pub id: u32,
+    /// 最後に検出されたときの特徴点(入力画像の座標)
+    pub landmarks: Option<FaceLandmarks>,
+    /// 特徴点から求めた画像面内の回転角[rad]
+    pub roll_angle: Option<f64>,
+}
+
+/// 描画するroiの値(入力画像の座標)．描画のたびに複製するため，位置の履歴はトラッカーにだけ持たせる
+#[derive(Clone, Debug)]
+pub struct RoiSnapshot {
+    pub id: u32,
+    pub rect: Rect,
+    /// 匿名化で覆う矩形(TrackerRoi::cover_rect)
+    pub cover_rect: Rect,
+    pub state: RoiState,
+    /// 見失ってからの更新の回数．検出されている場合は0
+    pub missed_updates: u32,
+    /// 最後に検出されたときの特徴点
+    pub landmarks: Option<FaceLandmarks>,
+    /// 特徴点から求めた画像面内の回転角[rad]
+    pub roll_angle: Option<f64>,
+}
+
+impl RoiSnapshot {
+    /// 矩形と特徴点を平行移動した写し(描画ループで外挿した位置に描く場合)
+    pub fn translated(&self, dx: f64, dy: f64) -> Self {
+        let mut roi = self.clone();
+        for rect in [&mut roi.rect, &mut roi.cover_rect] {
+            rect.x += dx;
+            rect.y += dy;
+        }
+        if let Some(landmarks) = roi.landmarks.as_mut() {
+            landmarks.translate(dx, dy);
+        }
+        roi
+    }
+}
+
+/// 各トラックの位置の履歴をCSV(id,update,x,y,width,height)にする
+/// - to_output: 入力画像の座標から出力する座標への変換
+pub fn trajectories_csv(rois: &[TrackerRoi], to_output: &Transform) -> String {
+    let mut csv = String::from("id,update,x,y,width,height\n");
+    for roi in rois {
+        for point in roi.history.iter() {
+            let rect = to_output.apply_rect(&point.rect);
+            // Stringへの書き込みは失敗しない
+            let _ = writeln!(
+                csv,
+                "{},{},{:.1},{:.1},{:.1},{:.1}",
+                roi.id, point.update, rect.x, rect.y, rect.width, rect.height
+            );
+        }
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::landmark::LandmarkPoint;
+    use super::*;
+
+    #[test]
+    fn cover_rect_grows_while_not_detected() {
+        let mut roi = TrackerRoi::new(0, 100.0, 100.0, 40.0, 40.0);
+        roi.velocity_x = 3.0;
+        roi.velocity_y = 4.0;
+        let velocity = RoiGrowthOptions::default();
+        let rate = RoiGrowthOptions {
+            mode: RoiGrowthMode::Rate,
+            ..Default::default()
+        };
+        // 検出されている間は広げない
+        assert_eq!(roi.cover_rect(&velocity), roi.rect());
+
+        roi.not_detected();
+        roi.not_detected();
+        assert_eq!(roi.missed_updates(), 2);
+        assert_eq!(roi.cover_rect(&velocity), Rect::new(90.0, 90.0, 60.0, 60.0));
+        assert_eq!(roi.cover_rect(&rate), Rect::new(94.0, 94.0, 52.0, 52.0));
+
+        // 上限の倍率を超えて広げない
+        for _ in 0..10 {
+            roi.not_detected();
+        }
+        assert_eq!(roi.cover_rect(&velocity), Rect::new(80.0, 80.0, 80.0, 80.0));
+
+        roi.detected();
+        assert_eq!(roi.cover_rect(&velocity), roi.rect());
+    }
+
+    #[test]
+    fn estimate_carries_landmarks_and_roll() {
+        let mut roi = TrackerRoi::new(3, 0.0, 0.0, 40.0, 40.0);
+        assert_eq!(roi.estimate().roll_angle, None);
+
+        let point = |x, y| LandmarkPoint { x, y };
+        roi.landmarks = Some(FaceLandmarks {
+            left_eye: point(10.0, 10.0),
+            right_eye: point(30.0, 30.0),
+            nose: point(20.0, 20.0),
+            left_mouth: point(12.0, 30.0),
+            right_mouth: point(28.0, 30.0),
+        });
+        let estimate = roi.estimate();
+        assert_eq!(estimate.id, 3);
+        assert_eq!(estimate.landmarks, roi.landmarks);
+        assert_eq!(estimate.roll_angle, Some(std::f64::consts::FRAC_PI_4));
+
+        // 平行移動しても回転角は変わらない
+        let snapshot = roi
+            .snapshot(&RoiGrowthOptions::default(), estimate)
+            .translated(5.0, 0.0);
+        assert_eq!(snapshot.landmarks.unwrap().left_eye, point(15.0, 10.0));
+        assert_eq!(snapshot.roll_angle, Some(std::f64::consts::FRAC_PI_4));
+    }
+
+    #[test]
+    fn history_is_bounded_and_exported() {
+        let mut roi = TrackerRoi::new(7, 10.0, 20.0, 30.0, 40.0);
+        for update in 1..=4 {
+            roi.tl_x += 1.0;
+            roi.record(update, 3);
+        }
+        let updates = roi
+            .history
+            .iter()
+            .map(|point| point.update)
+            .collect::<Vec<_>>();
+        assert_eq!(updates, vec![2, 3, 4]);
+
+        // 入力画像の2倍の大きさのビデオの座標で出力する
+        let csv = trajectories_csv(&[roi], &Transform::scale(2.0, 2.0));
+        assert_eq!(
+            csv,
+            "id,update,x,y,width,height\n\
+             7,2,24.0,40.0,60.0,80.0\n\
+             7,3,26.0,40.0,60.0,80.0\n\
+             7,4,28.0,40.0,60.0,80.0\n"
+        );
+    }
+}
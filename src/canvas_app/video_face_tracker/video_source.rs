@@ -0,0 +1,171 @@
+use super::super::dom_utils::window;
+use super::super::geometry::Rect;
+use super::camera::{
+    get_camera_stream, set_stream_enabled, stop_stream, CameraSelection, VideoOptions,
+};
+use super::synthetic::SyntheticVideo;
+use crate::error::AppError;
+
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+
+/// ビデオの入力ソース
+#[derive(Clone, Debug, PartialEq)]
+pub enum VideoSource {
+    /// webカメラ
+    Camera(CameraSelection),
+    /// ユーザーが選択したローカルの動画ファイル(オブジェクトURL)
+    File(String),
+    /// 画面またはウィンドウのキャプチャ
+    Screen,
+    /// 真値の既知な顔が動く合成映像(カメラの無い環境でのテスト・デモ用)
+    Synthetic,
+}
+
+impl Default for VideoSource {
+    fn default() -> Self {
+        VideoSource::Camera(Default::default())
+    }
+}
+
+impl VideoSource {
+    /// 再生位置を操作できるかどうか
+    pub fn is_seekable(&self) -> bool {
+        matches!(self, VideoSource::File(_))
+    }
+
+    /// 一時停止時にトラックを停止し，再開時にストリームを取得し直すかどうか．
+    /// カメラはトラックを停止しないとランプが消えないため．画面共有は取得し直すと再度選択が必要になるため無効化に留める
+    pub fn reopens_on_resume(&self) -> bool {
+        matches!(self, VideoSource::Camera(_))
+    }
+}
+
+/// 画面共有のストリームを取得する．共有する画面やウィンドウはユーザーが選択する
+async fn get_display_stream() -> Result<web_sys::MediaStream, AppError> {
+    let mut media_constraints = web_sys::DisplayMediaStreamConstraints::new();
+    media_constraints.audio(&JsValue::FALSE);
+    media_constraints.video(&JsValue::TRUE);
+
+    let stream_promise = window()
+        .navigator()
+        .media_devices()?
+        .get_display_media_with_constraints(&media_constraints)?;
+
+    JsFuture::from(stream_promise)
+        .await?
+        .dyn_into::<web_sys::MediaStream>()
+        .map_err(|js_value| {
+            AppError::JsError(format!(
+                "Sanity Check. Cannot convert from: {js_value:?} into web_sys::MediaStream"
+            ))
+        })
+}
+
+/// 開いたソースのストリーム
+#[derive(Default)]
+pub struct SourceStream {
+    /// ソースのストリーム(ファイルの場合はNone)
+    media_stream: Option<web_sys::MediaStream>,
+    /// 合成映像(ストリームの間描画を続ける)
+    synthetic: Option<SyntheticVideo>,
+}
+
+impl SourceStream {
+    /// ソースのストリーム(ファイルの場合はNone)
+    pub fn media_stream(&self) -> Option<&web_sys::MediaStream> {
+        self.media_stream.as_ref()
+    }
+
+    /// ストリームのトラックの有効・無効を切り替える
+    pub fn set_enabled(&self, enabled: bool) {
+        if let Some(media_stream) = self.media_stream() {
+            set_stream_enabled(media_stream, enabled);
+        }
+    }
+
+    /// 合成映像の場合は真値の矩形(ビデオの座標)
+    pub fn ground_truth(&self) -> Option<Vec<Rect>> {
+        self.synthetic.as_ref().map(SyntheticVideo::ground_truth)
+    }
+}
+
+/// ソースのストリームを取得する．ファイルの場合はストリームを持たない
+pub async fn open_source(
+    source: &VideoSource,
+    video_options: &VideoOptions,
+) -> Result<SourceStream, AppError> {
+    match source {
+        VideoSource::Camera(camera_selection) => Ok(SourceStream {
+            media_stream: Some(get_camera_stream(camera_selection, video_options).await?),
+            synthetic: None,
+        }),
+        VideoSource::File(_) => Ok(SourceStream::default()),
+        VideoSource::Screen => Ok(SourceStream {
+            media_stream: Some(get_display_stream().await?),
+            synthetic: None,
+        }),
+        VideoSource::Synthetic => {
+            let synthetic = SyntheticVideo::new()?;
+            Ok(SourceStream {
+                media_stream: Some(synthetic.capture_stream()?),
+                synthetic: Some(synthetic),
+            })
+        }
+    }
+}
+
+/// ストリームの全てのビデオトラックが終了したかどうか(画面共有の停止など)
+pub fn is_stream_ended(media_stream: &web_sys::MediaStream) -> bool {
+    media_stream
+        .get_video_tracks()
+        .iter()
+        .filter_map(|track| track.dyn_into::<web_sys::MediaStreamTrack>().ok())
+        .all(|track| track.ready_state() == web_sys::MediaStreamTrackState::Ended)
+}
+
+/// ビデオ要素のsrc属性を削除する．空文字列を設定するとページのURLを動画として読み込もうとするため属性ごと削除する
+fn remove_src(video: &web_sys::HtmlVideoElement) {
+    if let Err(e) = video.remove_attribute("src") {
+        log::error!("{e:?}");
+    }
+}
+
+/// ビデオ要素からソースを外し，読み込み直して保持しているリソースを解放する
+pub fn detach_source(video: &web_sys::HtmlVideoElement) {
+    video.set_src_object(None);
+    remove_src(video);
+    video.load();
+}
+
+/// ビデオ要素にソースを設定する
+pub fn attach_source(
+    video: &web_sys::HtmlVideoElement,
+    source: &VideoSource,
+    media_stream: Option<&web_sys::MediaStream>,
+) {
+    match source {
+        VideoSource::Camera(_) | VideoSource::Screen | VideoSource::Synthetic => {
+            video.set_loop(false);
+            remove_src(video);
+            video.set_src_object(media_stream);
+        }
+        VideoSource::File(object_url) => {
+            video.set_src_object(None);
+            video.set_muted(true); // 非同期処理の後でも再生できるようにミュートする
+            video.set_src(object_url);
+        }
+    }
+}
+
+/// ソースを解放する．カメラのトラックを停止し，オブジェクトURLを破棄する．合成映像の描画はsource_streamと共に停止する
+pub fn release_source(source: &VideoSource, source_stream: &SourceStream) {
+    if let Some(media_stream) = source_stream.media_stream() {
+        stop_stream(media_stream);
+    }
+    if let VideoSource::File(object_url) = source {
+        if let Err(e) = web_sys::Url::revoke_object_url(object_url) {
+            log::error!("{e:?}");
+        }
+    }
+}